@@ -1,54 +1,270 @@
 use anyhow::{Context, Result, anyhow, bail};
-use serde::Deserialize;
-use std::{env, ffi, fmt, fs, path, process};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::{env, ffi, fmt, fs, net, path, process, rc, sync, thread, time};
 use toml;
 use walkdir::WalkDir;
 
-use crate::{logging, systemd};
+use crate::cli::HookType;
+use crate::exec::CommandRunner;
+use crate::status_cache::StatusCache;
+use crate::{logging, systemd, timestamp, unit_file};
 
-const CONFIG_FILE_NAME: &str = "config.toml";
+pub const LOG_PRIORITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+const OVERRIDE_FILE_NAME: &str = "units_override.conf";
+const ENV_FILE_NAME: &str = "units_env.conf";
+const STOP_TIMEOUT_FILE_NAME: &str = "stop_timeout.conf";
+const WATCHDOG_FILE_NAME: &str = "watchdog.conf";
+const FD_STORE_FILE_NAME: &str = "fd_store.conf";
+const RESOURCE_LIMITS_FILE_NAME: &str = "resource_limits.conf";
+const ORDERING_FILE_NAME: &str = "ordering.conf";
+const BINDING_FILE_NAME: &str = "binding.conf";
+const LOGGING_FILE_NAME: &str = "logging.conf";
+const START_LIMIT_FILE_NAME: &str = "start_limit.conf";
+const LOG_NAMESPACE_FILE_NAME: &str = "log_namespace.conf";
+const TMPFILES_DIR: &str = "/etc/tmpfiles.d";
+const USER_TMPFILES_DIR: &str = ".config/user-tmpfiles.d";
+const SYSUSERS_DIR: &str = "/etc/sysusers.d";
+const SELINUX_ENFORCE_PATH: &str = "/sys/fs/selinux/enforce";
+const FD_STORE_DROP_IN: &str = "[Service]\nFileDescriptorStore=yes\n";
+const DEFAULT_WATCHDOG_SEC: u32 = 30;
+const ACTIVE_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+const CPU_SAMPLE_INTERVAL: time::Duration = time::Duration::from_millis(100);
+const HEALTH_CHECK_RETRY_DELAY: time::Duration = time::Duration::from_secs(1);
 
 pub struct App {
     pub name: String,
     app_dir: path::PathBuf,
+    config_path: path::PathBuf,
+    config: AppConfig,
     systemd_dir: path::PathBuf,
+    // set by --install-dir, files are copied there without touching the system
+    install_dir_overridden: bool,
     use_user: bool,
+    runner: rc::Rc<dyn CommandRunner>,
+    status_cache: sync::Arc<sync::Mutex<StatusCache>>,
+}
+
+// the runner and status cache are shared handles, so they're left out
+impl fmt::Debug for App {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("App")
+            .field("name", &self.name)
+            .field("app_dir", &self.app_dir)
+            .field("config_path", &self.config_path)
+            .field("config", &self.config)
+            .field("systemd_dir", &self.systemd_dir)
+            .field("install_dir_overridden", &self.install_dir_overridden)
+            .field("use_user", &self.use_user)
+            .finish_non_exhaustive()
+    }
 }
 
 impl App {
-    pub fn new(name: &str) -> Result<Self> {
-        let config_path = path::PathBuf::from(name).join(CONFIG_FILE_NAME);
-        let config_str = fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to find config file at {}", config_path.display()))?;
-        let config: AppConfig = toml::from_str(&config_str)?;
-
-        let exe_path = env::current_exe()?;
-        let repo_dir = exe_path
-            .parent()
-            .ok_or_else(|| anyhow!("Failed to find current directory"))?
-            .to_path_buf();
-        let app_dir = repo_dir.join(name);
+    pub fn new(
+        name: &str,
+        repo_dir: &path::Path,
+        app_dir: path::PathBuf,
+        config_path: Option<path::PathBuf>,
+        runner: rc::Rc<dyn CommandRunner>,
+        status_cache: sync::Arc<sync::Mutex<StatusCache>>,
+    ) -> Result<Self> {
+        if !App::is_path_safe(repo_dir, &app_dir) {
+            bail!("App {name} is outside of the repo {}", repo_dir.display());
+        }
+        let config_path = config_path.unwrap_or_else(|| app_dir.join(CONFIG_FILE_NAME));
+        let config = read_config(&config_path)?;
+
+        config.validate(name, &app_dir).map_err(|errors| {
+            anyhow!(
+                "Invalid config {}:\n  {}",
+                config_path.display(),
+                errors.join("\n  ")
+            )
+        })?;
 
         Ok(App {
             name: String::from(name),
             app_dir,
-            systemd_dir: path::PathBuf::from(config.systemd.install_location),
+            config_path,
+            systemd_dir: path::PathBuf::from(&config.systemd.install_location),
+            install_dir_overridden: false,
             use_user: config.systemd.use_user,
+            config,
+            runner,
+            status_cache,
         })
     }
 
+    // registered apps are symlinks out of the repo, so only the directory
+    // holding the app has to resolve to somewhere inside it
+    pub fn is_path_safe(repo_dir: &path::Path, app_dir: &path::Path) -> bool {
+        let Ok(relative) = app_dir.strip_prefix(repo_dir) else {
+            return false;
+        };
+        if relative
+            .components()
+            .any(|c| !matches!(c, path::Component::Normal(_)))
+        {
+            return false;
+        }
+
+        let (Some(parent), Ok(repo_dir)) = (app_dir.parent(), repo_dir.canonicalize()) else {
+            return false;
+        };
+        match parent.canonicalize() {
+            Ok(parent) => parent.starts_with(repo_dir),
+            // nothing to escape through yet, loading the config reports it missing
+            Err(_) => true,
+        }
+    }
+
+    // copies the app, renaming every unit named after it and the names inside
+    // them, so the clone doesn't install over the original's timers or sockets
+    pub fn clone_to(&self, dest_repo: &path::Path, new_name: Option<&str>) -> Result<()> {
+        let new_name = new_name.unwrap_or(&self.name);
+        let dest_dir = dest_repo.join(new_name);
+        if !App::is_path_safe(dest_repo, &dest_dir) {
+            bail!(
+                "App name {new_name} would put it outside of {}",
+                dest_repo.display()
+            );
+        }
+        if dest_dir.symlink_metadata().is_ok() {
+            bail!("{} already exists", dest_dir.display());
+        }
+
+        // "myapp.timer" -> "web.timer", "myapp-worker.service" -> "web-worker.service"
+        let renamed = |file_name: &ffi::OsStr| {
+            let rest = file_name.to_str()?.strip_prefix(&self.name)?;
+            (rest.starts_with('.') || rest.starts_with('-')).then(|| format!("{new_name}{rest}"))
+        };
+        // longest first, so "myapp-worker" is replaced before "myapp"
+        let mut stems = Vec::new();
+        for entry in fs::read_dir(&self.app_dir)? {
+            let file_name = entry?.file_name();
+            if renamed(&file_name).is_none() {
+                continue;
+            }
+            let file_name = file_name.to_string_lossy();
+            let stem = file_name.split('.').next().unwrap_or_default();
+            if !stems.iter().any(|s| s == stem) {
+                stems.push(stem.to_string());
+            }
+        }
+        stems.sort_by_key(|s| std::cmp::Reverse(s.len()));
+
+        for entry in WalkDir::new(&self.app_dir).sort_by_file_name() {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(&self.app_dir)?;
+            let mut components = relative.components();
+            let first = components.next().map(|c| c.as_os_str());
+            let new_first = first.and_then(renamed);
+            let target = match &new_first {
+                Some(new_first) if components.as_path().as_os_str().is_empty() => {
+                    dest_dir.join(new_first)
+                }
+                Some(new_first) => dest_dir.join(new_first).join(components.as_path()),
+                None => dest_dir.join(relative),
+            };
+
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&target)
+                    .with_context(|| format!("Failed to create {}", target.display()))?;
+            } else if let (Some(_), Ok(unit)) = (&new_first, fs::read_to_string(entry.path())) {
+                let unit = stems.iter().fold(unit, |unit, stem| {
+                    let new_stem = format!("{new_name}{}", &stem[self.name.len()..]);
+                    replace_name(&unit, stem, &new_stem)
+                });
+                fs::write(&target, unit)
+                    .with_context(|| format!("Failed to write {}", target.display()))?;
+            } else {
+                fs::copy(entry.path(), &target)
+                    .with_context(|| format!("Failed to copy {}", entry.path().display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    pub fn reload_config(&self) -> Result<AppConfig> {
+        read_config(&self.config_path)
+    }
+
+    pub fn use_user(&self) -> bool {
+        self.use_user
+    }
+
+    pub fn app_dir(&self) -> &path::Path {
+        &self.app_dir
+    }
+
+    pub fn systemd_dir(&self) -> &path::Path {
+        &self.systemd_dir
+    }
+
+    pub fn set_install_dir(&mut self, dir: path::PathBuf) {
+        self.systemd_dir = dir;
+        self.install_dir_overridden = true;
+    }
+
+    pub fn install_dir_overridden(&self) -> bool {
+        self.install_dir_overridden
+    }
+
     pub fn get_status(&self) -> Result<AppStatus> {
+        if let Some(status) = self.status_cache.lock().unwrap().get(&self.name) {
+            return Ok(status);
+        }
+
+        let status = self.query_status()?;
+        self.status_cache
+            .lock()
+            .unwrap()
+            .insert(&self.name, status.clone());
+        Ok(status)
+    }
+
+    fn query_status(&self) -> Result<AppStatus> {
         if !self.files_installed()? {
             return Ok(AppStatus::NotInstalled);
         }
+        if self.is_sysext() && !self.extension_merged()? {
+            return Ok(AppStatus::Installed);
+        }
+        if self.get_unit_load_state()? == "masked" {
+            return Ok(AppStatus::Masked);
+        }
 
-        let service_name = format!("{}.service", self.name);
-        let is_active = systemd::is_active(&service_name, !self.use_user)?;
-        if is_active {
+        // the app only counts as running when every one of its units is
+        let mut all_active = true;
+        for unit in self.unit_names() {
+            all_active &= systemd::is_active(&unit, !self.use_user, self.runner.as_ref())?;
+        }
+        if all_active {
             return Ok(AppStatus::Running);
         }
+        if !self.get_failed_units()?.is_empty() {
+            if self.get_service_start_limit_hit()? {
+                return Ok(AppStatus::StartLimitHit);
+            }
+            return Ok(AppStatus::Failed);
+        }
 
-        let is_enabled = systemd::is_active(&service_name, !self.use_user)?;
+        let service_name = format!("{}.service", self.name);
+        let is_enabled = systemd::is_enabled(&service_name, !self.use_user, self.runner.as_ref())?;
         if is_enabled {
             Ok(AppStatus::Stopped)
         } else {
@@ -56,33 +272,230 @@ impl App {
         }
     }
 
-    pub fn install(&self, dry_run: bool, force: bool) -> Result<()> {
+    pub fn get_unit_load_state(&self) -> Result<String> {
+        self.show_property("LoadState")
+    }
+
+    // systemd refuses to start the service again until the failure is reset
+    pub fn get_service_start_limit_hit(&self) -> Result<bool> {
+        // the unit is plain failed, only the result says why
+        Ok(self.show_property("Result")? == "start-limit-hit")
+    }
+
+    pub fn reset_failed(&self) -> Result<()> {
+        let mut args = vec![String::from("reset-failed")];
+        args.extend(self.unit_names());
+        let args = self.prepare_systemctl_args(args);
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to reset the failed state of {}", self.name);
+        }
+        Ok(())
+    }
+
+    pub fn unmask(&self) -> Result<()> {
+        let args = self.prepare_systemctl_args(vec![
+            String::from("unmask"),
+            format!("{}.service", self.name),
+        ]);
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to unmask {}.service", self.name);
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> Result<bool> {
+        let service_name = format!("{}.service", self.name);
+        systemd::is_enabled(&service_name, !self.use_user, self.runner.as_ref())
+    }
+
+    // static units have no [Install] section, so nothing pulls them in at boot
+    pub fn is_static(&self) -> Result<bool> {
+        Ok(self.show_property("UnitFileState")? == "static")
+    }
+
+    pub fn enable(&self) -> Result<()> {
+        let args = self.prepare_systemctl_args(vec![
+            String::from("enable"),
+            format!("{}.service", self.name),
+        ]);
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to enable {}.service", self.name);
+        }
+        Ok(())
+    }
+
+    pub fn disable(&self) -> Result<()> {
+        let args = self.prepare_systemctl_args(vec![
+            String::from("disable"),
+            format!("{}.service", self.name),
+        ]);
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to disable {}.service", self.name);
+        }
+        Ok(())
+    }
+
+    pub fn get_failed_units(&self) -> Result<Vec<String>> {
+        let mut failed = Vec::new();
+        for unit in self.unit_names() {
+            if systemd::is_failed(&unit, !self.use_user, self.runner.as_ref())? {
+                failed.push(unit);
+            }
+        }
+        Ok(failed)
+    }
+
+    // e.g. "App myapp is failed: myapp-worker.service exited with code 1"
+    pub fn failure_reason(&self) -> Result<Option<String>> {
+        let failed = self.get_failed_units()?;
+        if failed.is_empty() {
+            return Ok(None);
+        }
+
+        let mut reasons = Vec::new();
+        for unit in &failed {
+            let code = self.show_unit_property(unit, "ExecMainStatus")?;
+            reasons.push(format!("{unit} exited with code {code}"));
+        }
+        Ok(Some(format!(
+            "App {} is failed: {}",
+            self.name,
+            reasons.join(", ")
+        )))
+    }
+
+    pub fn install(
+        &self,
+        dry_run: bool,
+        overwrite: bool,
+        env: &[String],
+        strict: bool,
+        health_check: bool,
+    ) -> Result<()> {
         let app_files = self.get_app_files()?;
         if app_files.is_empty() {
             bail!("No files found for app {}", self.name)
         }
 
+        self.validate()?;
+
+        // lint the unit files before anything is created
+        let mut warning_count = 0;
+        for file in &app_files {
+            if !matches!(
+                file.extension().and_then(|e| e.to_str()),
+                Some("service" | "timer" | "socket")
+            ) {
+                continue;
+            }
+            for warning in self.verify_unit_syntax(file)? {
+                let filename = file.file_name().unwrap_or_default().to_string_lossy();
+                logging::warn(&format!("{filename}: {warning}"));
+                warning_count += 1;
+            }
+        }
+        if strict && warning_count > 0 {
+            bail!("Found {warning_count} unit file warnings, not installing because of --strict")
+        }
+        if !dry_run {
+            self.install_directory()?;
+        }
+
+        let drop_ins = self.drop_ins(env)?;
+        let tmpfiles_config = self.tmpfiles_d_config();
+        let sysusers_config = self.sysusers_d_config();
+
         if dry_run {
             logging::info(&format!("[DRY RUN] Would install app {}", self.name));
+            if !self.systemd_dir.exists() && self.config.systemd.create_install_dir {
+                logging::info(&format!(
+                    "[DRY RUN] Would create directory {}",
+                    self.systemd_dir.display()
+                ));
+            }
+            if !self.install_dir_overridden {
+                self.log_dry_run_hook(HookType::PreInstall);
+                if self.config.systemd.create_service_user {
+                    logging::info(&format!(
+                        "[DRY RUN] Would run useradd --system --no-create-home --shell /usr/sbin/nologin {} if the user doesn't exist",
+                        self.service_user()
+                    ));
+                }
+                if let Some(content) = &sysusers_config {
+                    let sysusers_path = self.sysusers_d_path();
+                    logging::info(&format!(
+                        "[DRY RUN] Would write {}:\n{content}",
+                        sysusers_path.display()
+                    ));
+                    logging::info(&format!(
+                        "[DRY RUN] Would run systemd-sysusers {}.conf",
+                        self.name
+                    ));
+                }
+                if let Some(content) = &tmpfiles_config {
+                    let tmpfiles_path = self.tmpfiles_d_path()?;
+                    logging::info(&format!(
+                        "[DRY RUN] Would write {}:\n{content}",
+                        tmpfiles_path.display()
+                    ));
+                    logging::info(&format!(
+                        "[DRY RUN] Would run systemd-tmpfiles --create {}",
+                        tmpfiles_path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                    ));
+                }
+            }
+            if self.config.systemd.atomic_install {
+                self.install_atomically(true, overwrite)?;
+            }
             for file in &app_files {
                 let unit_name = file.strip_prefix(&self.app_dir)?;
                 let target_path = self.systemd_dir.join(unit_name);
                 logging::info(&format!(
                     "[DRY RUN] Would copy {} to {}",
-                    file.to_str().unwrap(),
-                    target_path.to_str().unwrap()
+                    file.display(),
+                    target_path.display()
                 ));
             }
-            if self.use_user {
+            if !self.install_dir_overridden && self.restore_selinux_context() {
+                logging::info("[DRY RUN] Would run restorecon -v on the copied files");
+            }
+            for (file_name, content) in &drop_ins {
+                if let Some(content) = content {
+                    logging::info(&format!(
+                        "[DRY RUN] Would write {}:\n{content}",
+                        self.drop_in_dir().join(file_name).display()
+                    ));
+                }
+            }
+            if self.install_dir_overridden {
                 logging::info(&format!(
-                    "[DRY RUN] Would reload systemd and start {}.servie as user",
+                    "[DRY RUN] Would not start {}.service because --install-dir is set",
                     self.name
                 ));
             } else {
-                logging::info(&format!(
-                    "[DRY RUN] Would reload systemd and start {}.service",
-                    self.name
-                ));
+                if self.is_sysext() {
+                    logging::info(&format!(
+                        "[DRY RUN] Would refresh system extensions and start {}.service",
+                        self.name
+                    ));
+                } else if self.use_user {
+                    logging::info(&format!(
+                        "[DRY RUN] Would reload systemd and start {}.service as user",
+                        self.name
+                    ));
+                } else {
+                    logging::info(&format!(
+                        "[DRY RUN] Would reload systemd and start {}.service",
+                        self.name
+                    ));
+                }
+                self.log_dry_run_hook(HookType::PostInstall);
+                if health_check && let Some(check) = &self.config.health_check {
+                    logging::info(&format!("[DRY RUN] Would check {}", check.url));
+                }
             }
             return Ok(());
         }
@@ -92,68 +505,164 @@ impl App {
             let unit_name = file.strip_prefix(&self.app_dir)?;
             let target_path = self.systemd_dir.join(unit_name);
 
-            if target_path.exists() && !force {
+            if target_path.exists() && !overwrite {
                 logging::warn(&format!(
-                    "File {} already exists. Use --force to overwrite.",
-                    target_path.to_str().unwrap()
+                    "File {} already exists. Use --overwrite to overwrite.",
+                    target_path.display()
                 ));
-                bail!("File already exsists and force not used")
+                bail!("File already exsists and overwrite not used")
+            }
+        }
+
+        if !self.install_dir_overridden {
+            self.run_configured_hook(HookType::PreInstall)?;
+
+            if self.config.systemd.create_service_user {
+                self.create_service_account()?;
             }
+            self.create_sysusers_d()?;
+            // the directories may be owned by the service user, so create them after it
+            self.create_tmpfiles_d()?;
         }
 
         // copy files
-        for file in &app_files {
-            let unit_name = file.strip_prefix(&self.app_dir)?;
-            let target_path = self.systemd_dir.join(unit_name);
-            let filename = target_path
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
+        if self.config.systemd.atomic_install {
+            self.install_atomically(false, overwrite)?;
+        } else {
+            for file in &app_files {
+                let unit_name = file.strip_prefix(&self.app_dir)?;
+                let target_path = self.systemd_dir.join(unit_name);
+                let filename = target_path
+                    .file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+
+                self.copy_with_permissions(file, &target_path)?;
+                logging::info(&format!("Copied {filename}"))
+            }
+        }
+        if !self.install_dir_overridden {
+            self.apply_selinux_context()?;
+        }
+
+        for (file_name, content) in &drop_ins {
+            if let Some(content) = content {
+                self.write_drop_in(file_name, content)?;
+            }
+        }
 
-            fs::copy(file, &target_path).context(format!(
-                "Failed to copy {} to {}",
-                file.to_str().unwrap(),
-                target_path.to_str().unwrap(),
-            ))?;
-            logging::info(&format!("Copied {filename}"))
+        if self.install_dir_overridden {
+            logging::info(&format!(
+                "Not starting {}.service because --install-dir is set",
+                self.name
+            ));
+            return Ok(());
         }
 
         // reload systemd, start the main service
-        let args = self.prepare_systemctl_args(vec![String::from("daemon-relaod")]);
-        process::Command::new("systemctl").args(args).status()?;
+        if self.is_sysext() {
+            self.sysext(&["refresh"])?;
+        } else {
+            self.daemon_reload()?;
+        }
 
         let service_name = format!("{}.service", self.name);
-        let args = self.prepare_systemctl_args(vec![String::from("start"), service_name]);
-        process::Command::new("systemctl").args(args).status()?;
+        let args = self.prepare_systemctl_args(vec![String::from("start"), service_name.clone()]);
+        // the only failure signal for the types that skip wait_for_active
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to start {service_name}");
+        }
+
+        match self.service_type()?.as_str() {
+            // oneshot services are expected to exit, and systemctl start only
+            // returns for notify services once they have sent READY=1
+            "oneshot" | "notify" => {}
+            _ => self.wait_for_active(ACTIVE_TIMEOUT)?,
+        }
+
+        self.run_configured_hook(HookType::PostInstall)?;
+
+        // a failing service is left running so it can be inspected
+        if health_check && let Some(check) = &self.config.health_check {
+            if self.check_health(check)? {
+                logging::info(&format!("Health check of {} passed", self.name));
+            } else {
+                logging::error(&format!(
+                    "Health check of {} failed after {} attempt(s), {} didn't respond",
+                    self.name, check.retries, check.url
+                ));
+            }
+        }
 
         Ok(())
     }
 
-    pub fn uninstall(&self, dry_run: bool, force: bool) -> Result<()> {
-        let app_files = self.get_app_files().context("Failed to get app files")?;
+    pub fn check_health(&self, check: &HealthCheck) -> Result<bool> {
+        let timeout = time::Duration::from_secs(check.timeout_secs);
+        for attempt in 1..=check.retries {
+            if http_get_succeeds(&check.url, timeout)? {
+                return Ok(true);
+            }
+            if attempt < check.retries {
+                thread::sleep(HEALTH_CHECK_RETRY_DELAY);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn uninstall(
+        &self,
+        dry_run: bool,
+        no_confirm: bool,
+        keep_dirs: bool,
+        ignore_remove_errors: bool,
+        remove_overrides: bool,
+    ) -> Result<bool> {
+        let app_files = self.target_paths().context("Failed to get app files")?;
         if app_files.is_empty() {
             bail!("No files found for app {}", self.name)
         }
 
         if dry_run {
+            self.log_dry_run_hook(HookType::PreUninstall);
             logging::info(&format!(
                 "[DRY RUN] Would stop and disable {}.service",
                 self.name
             ));
 
             for file in app_files {
+                logging::info(&format!("[DRY RUN] Would remove {}", file.display()));
+            }
+            if self.tmpfiles_d_config().is_some() {
+                let tmpfiles_path = self.tmpfiles_d_path()?;
+                logging::info(&format!(
+                    "[DRY RUN] Would run systemd-tmpfiles --remove {} and remove {}",
+                    tmpfiles_path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                    tmpfiles_path.display()
+                ));
+            }
+            if self.sysusers_d_config().is_some() {
                 logging::info(&format!(
-                    "[DRY RUN] Would remove {}",
-                    file.to_str().unwrap()
+                    "[DRY RUN] Would remove {}, keeping its users and groups",
+                    self.sysusers_d_path().display()
                 ));
             }
+            if remove_overrides {
+                for file in self.list_drop_ins()? {
+                    logging::info(&format!("[DRY RUN] Would remove {}", file.display()));
+                }
+            }
+            self.log_dry_run_hook(HookType::PostUninstall);
 
-            return Ok(());
+            return Ok(true);
         }
 
-        if !force {
+        if !no_confirm {
             let confirmation = dialoguer::Confirm::new()
                 .with_prompt(format!("Are you sure you want to uninstall {}?", self.name))
                 .default(false)
@@ -162,110 +671,2697 @@ impl App {
 
             if !confirmation {
                 logging::info("Uninstall cancelled");
-                return Ok(());
+                return Ok(false);
             }
         }
 
+        self.run_configured_hook(HookType::PreUninstall)?;
+
+        // limit how long systemd waits for the service to stop
+        if let Some(timeout) = self.config.systemd.stop_timeout_secs {
+            self.write_drop_in(
+                STOP_TIMEOUT_FILE_NAME,
+                &format!("[Service]\nTimeoutStopSec={timeout}\n"),
+            )?;
+            self.daemon_reload()?;
+        }
+
         // stop service if running
         let service_name = format!("{}.service", self.name);
-        let args = self.prepare_systemctl_args(vec![String::from("stop"), service_name]);
-        let _ = process::Command::new("systemctl").args(args).status();
+        let args = self.prepare_systemctl_args(vec![
+            String::from("stop"),
+            String::from("--job-mode=fail"),
+            service_name,
+        ]);
+        let stopped = self.systemctl(args).is_ok_and(|s| s.success());
+        if !stopped {
+            logging::warn(&format!(
+                "Failed to stop {}.service, removing files anyway",
+                self.name
+            ));
+        }
 
+        // a file left behind would let systemd keep restarting the service
         for file in app_files {
-            let _ = fs::remove_file(&file);
-            logging::info(&format!("Removed file {}", file.to_str().unwrap()));
+            match fs::remove_file(&file) {
+                Ok(()) => logging::info(&format!("Removed file {}", file.display())),
+                Err(e) if ignore_remove_errors => {
+                    logging::warn(&format!("Failed to remove {}: {e}", file.display()))
+                }
+                Err(e) => {
+                    return Err(e).context(format!("Failed to remove {}", file.display()));
+                }
+            }
+        }
+
+        // every drop-in install may have written, whatever the config says now
+        for (file_name, _) in self.drop_ins(&[])? {
+            self.remove_drop_in(file_name, keep_dirs)?;
+        }
+        self.remove_drop_in(STOP_TIMEOUT_FILE_NAME, keep_dirs)?;
+        self.remove_tmpfiles_d()?;
+        self.remove_sysusers_d()?;
+
+        // whatever is left was added by hand or by `units override`
+        let drop_ins = self.list_drop_ins()?;
+        if remove_overrides {
+            for file in &drop_ins {
+                fs::remove_file(file)
+                    .with_context(|| format!("Failed to remove {}", file.display()))?;
+                logging::info(&format!("Removed file {}", file.display()));
+            }
+            let drop_in_dir = self.drop_in_dir();
+            if !keep_dirs && drop_in_dir.exists() && fs::read_dir(&drop_in_dir)?.next().is_none() {
+                fs::remove_dir(&drop_in_dir)
+                    .with_context(|| format!("Failed to remove {}", drop_in_dir.display()))?;
+                logging::info(&format!("Removed directory {}", drop_in_dir.display()));
+            }
+        } else if !drop_ins.is_empty() {
+            logging::info(&format!(
+                "Kept {} drop-in file(s) in {}, use --remove-overrides to remove them",
+                drop_ins.len(),
+                self.drop_in_dir().display()
+            ));
+        }
+
+        if !keep_dirs {
+            self.prune_empty_dirs()?;
         }
 
         // reload systemd
-        let args = self.prepare_systemctl_args(vec![String::from("daemon-reload")]);
-        process::Command::new("systemctl")
-            .args(args)
-            .status()
-            .context("Failed to reload systemd after stopping service and removing files")?;
+        if self.is_sysext() {
+            // unmerge drops every extension, so merge the remaining ones back
+            self.sysext(&["unmerge"])?;
+            self.sysext(&["refresh"])?;
+        } else {
+            self.daemon_reload()
+                .context("Failed to reload systemd after stopping service and removing files")?;
+        }
 
-        Ok(())
+        self.run_configured_hook(HookType::PostUninstall)?;
+
+        Ok(true)
     }
 
-    pub fn logs(&self) -> Result<()> {
-        let status = process::Command::new("journalctl")
-            .args(["-u", &format!("{}.service", self.name), "-f"])
-            .status()?;
+    pub fn run_hook(&self, hook: HookType) -> Result<()> {
+        let command = self
+            .hook_command(hook)
+            .ok_or_else(|| anyhow!("No {hook} hook configured for app {}", self.name))?;
+
+        logging::info(&format!("Running {hook} hook for {}", self.name));
+        let status = process::Command::new("sh")
+            .args(["-c", command])
+            .current_dir(&self.app_dir)
+            .stdout(process::Stdio::inherit())
+            .stderr(process::Stdio::inherit())
+            .status()
+            .with_context(|| format!("Failed to run {hook} hook for {}", self.name))?;
 
         if !status.success() {
-            return Err(anyhow!("Failed to show logs for '{}'", self.name));
+            bail!("The {hook} hook for {} exited with {status}", self.name);
         }
 
         Ok(())
     }
 
-    fn files_installed(&self) -> Result<bool> {
-        for entry in WalkDir::new(&self.app_dir).into_iter() {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() || path.file_name() == Some(ffi::OsStr::new(CONFIG_FILE_NAME)) {
-                continue;
-            }
+    // runs in the background so a slow alert doesn't hold up the next poll
+    pub fn notify(&self, status: &AppStatus) -> Result<()> {
+        let Some(command) = &self.config.alerts.notify_command else {
+            return Ok(());
+        };
 
-            let unit_name = path.strip_prefix(&self.app_dir)?;
-            let target_path = self.systemd_dir.join(unit_name);
-            if !target_path.exists() {
-                return Ok(false);
-            }
-        }
+        // its output goes to stderr to keep a --output ndjson stream parseable
+        let mut child = process::Command::new("sh")
+            .args(["-c", command])
+            .current_dir(&self.app_dir)
+            .env("APP_NAME", &self.name)
+            .env("STATUS", status.to_string())
+            .stdout(io::stderr())
+            .spawn()
+            .with_context(|| format!("Failed to run notify command for {}", self.name))?;
 
-        Ok(true)
+        let name = self.name.clone();
+        thread::spawn(move || match child.wait() {
+            Ok(status) if !status.success() => logging::warn(&format!(
+                "The notify command for {name} exited with {status}"
+            )),
+            Ok(_) => {}
+            Err(e) => logging::warn(&format!(
+                "Failed to wait for the notify command for {name}: {e}"
+            )),
+        });
+
+        Ok(())
     }
 
-    fn get_app_files(&self) -> Result<Vec<path::PathBuf>> {
-        let mut files = Vec::new();
+    pub fn security_score(&self) -> Result<SecurityReport> {
+        let checks: Vec<SecurityCheck> = serde_json::from_slice(&self.analyze_security(true)?)
+            .context("Failed to parse systemd-analyze output")?;
+        let warnings = checks
+            .into_iter()
+            .filter(|c| c.set == Some(false))
+            .map(|c| c.description)
+            .collect();
 
-        for entry in WalkDir::new(&self.app_dir).into_iter() {
-            let entry = entry?;
-            let path = entry.path();
+        let (score, exposure) = self.exposure_level()?;
+        Ok(SecurityReport {
+            score,
+            exposure,
+            warnings,
+        })
+    }
 
-            if path.is_dir() || path.file_name() == Some(ffi::OsStr::new(CONFIG_FILE_NAME)) {
-                continue;
-            }
+    // from 0.0 for a fully sandboxed service to 10.0 for an unrestricted one
+    pub fn get_security_score(&self) -> Result<f64> {
+        self.exposure_level().map(|(score, _)| score)
+    }
 
-            files.push(path.to_path_buf());
+    // the json output only has per-check exposures, so take the overall
+    // score from the summary line of the plain output
+    fn exposure_level(&self) -> Result<(f64, String)> {
+        let output = String::from_utf8(self.analyze_security(false)?)?;
+        output
+            .lines()
+            .find_map(parse_exposure_level)
+            .ok_or_else(|| anyhow!("Failed to find exposure level for {}", self.name))
+    }
+
+    pub fn transient_run(&self, command: &[String]) -> Result<()> {
+        let mut args = vec![
+            format!("--unit={}-transient", self.name),
+            String::from("--same-dir"),
+            String::from("--wait"),
+            String::from("--pipe"),
+            String::from("--collect"),
+        ];
+
+        // run as the same user and with the same environment as the service
+        let user = self.show_property("User")?;
+        if !user.is_empty() {
+            args.push(format!("--uid={user}"));
+        }
+        let group = self.show_property("Group")?;
+        if !group.is_empty() {
+            args.push(format!("--gid={group}"));
+        }
+        for variable in unit_file::split_quoted(&self.show_property("Environment")?) {
+            args.push(format!("--setenv={variable}"));
         }
 
-        Ok(files)
+        args.push(String::from("--"));
+        args.extend(command.iter().cloned());
+
+        let status = process::Command::new("systemd-run")
+            .args(self.prepare_systemctl_args(args))
+            .stdin(process::Stdio::inherit())
+            .stdout(process::Stdio::inherit())
+            .stderr(process::Stdio::inherit())
+            .status()
+            .context("Failed to run systemd-run")?;
+
+        if !status.success() {
+            bail!("Command for {} exited with {status}", self.name);
+        }
+
+        Ok(())
     }
 
-    fn prepare_systemctl_args(&self, mut args: Vec<String>) -> Vec<String> {
-        if self.use_user {
-            args.insert(0, "--user".to_string());
+    pub fn prune_empty_dirs(&self) -> Result<()> {
+        for target in self.target_paths()? {
+            let mut dir = target.parent();
+
+            // walk up toward systemd_dir, stopping at the first non-empty directory
+            while let Some(current) = dir {
+                if current == self.systemd_dir || !current.starts_with(&self.systemd_dir) {
+                    break;
+                }
+                if current.exists() {
+                    if fs::read_dir(current)?.next().is_some() {
+                        break;
+                    }
+                    fs::remove_dir(current)
+                        .with_context(|| format!("Failed to remove {}", current.display()))?;
+                    logging::info(&format!("Removed directory {}", current.display()));
+                }
+                dir = current.parent();
+            }
         }
-        args
+
+        Ok(())
     }
-}
 
-pub enum AppStatus {
-    NotInstalled,
-    Installed,
-    Stopped,
-    Running,
-}
+    pub fn logs(
+        &self,
+        unit: Option<&str>,
+        since: Option<&str>,
+        invocation: Option<&str>,
+    ) -> Result<()> {
+        let unit = self.log_unit(unit)?;
 
-impl fmt::Display for AppStatus {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            AppStatus::NotInstalled => write!(f, "Not Installed"),
-            AppStatus::Installed => write!(f, "Installed"),
-            AppStatus::Stopped => write!(f, "Stopped"),
-            AppStatus::Running => write!(f, "Running"),
+        let mut cmd = process::Command::new(&self.config.systemd.journalctl_bin.0);
+        cmd.args(["-u", &unit, "-f"]);
+        cmd.args(self.journal_namespace_arg());
+        if let Some(since) = since {
+            cmd.args(["--since", since]);
+        }
+        if let Some(invocation) = invocation {
+            cmd.arg(format!("_SYSTEMD_INVOCATION_ID={invocation}"));
+        }
+        let status = cmd.status()?;
+
+        if !status.success() {
+            return Err(anyhow!("Failed to show logs for '{}'", self.name));
         }
+
+        Ok(())
     }
-}
 
-#[derive(Deserialize)]
-struct AppConfig {
-    systemd: Systemd,
-}
+    // the entries logged so far, for structured output instead of following
+    pub fn log_records(
+        &self,
+        unit: Option<&str>,
+        since: Option<&str>,
+        invocation: Option<&str>,
+    ) -> Result<Vec<LogRecord>> {
+        let unit = self.log_unit(unit)?;
+        let mut args = vec![
+            String::from("-u"),
+            unit,
+            String::from("--output"),
+            String::from("json"),
+            String::from("--no-pager"),
+        ];
+        args.extend(self.journal_namespace_arg());
+        if let Some(since) = since {
+            args.extend([String::from("--since"), since.to_string()]);
+        }
+        if let Some(invocation) = invocation {
+            args.push(format!("_SYSTEMD_INVOCATION_ID={invocation}"));
+        }
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self
+            .runner
+            .run(&self.config.systemd.journalctl_bin.0, &args)
+            .with_context(|| format!("Failed to read logs for '{}'", self.name))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to read logs for '{}': {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut records = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            let timestamp = entry["__REALTIME_TIMESTAMP"]
+                .as_str()
+                .and_then(|t| t.parse().ok())
+                .map(|usec| {
+                    timestamp::format_rfc3339(time::UNIX_EPOCH + time::Duration::from_micros(usec))
+                });
+            let priority = entry["PRIORITY"]
+                .as_str()
+                .and_then(|p| p.parse::<usize>().ok())
+                .and_then(|p| LOG_PRIORITIES.get(p))
+                .map(|p| p.to_string());
+            records.push(LogRecord {
+                timestamp,
+                priority,
+                message: entry["MESSAGE"].as_str().unwrap_or_default().to_string(),
+                invocation_id: entry["_SYSTEMD_INVOCATION_ID"].as_str().map(str::to_string),
+            });
+        }
+        Ok(records)
+    }
+
+    fn log_unit(&self, unit: Option<&str>) -> Result<String> {
+        match unit {
+            Some(unit) => {
+                let unit = unit_name(unit);
+                if !self.unit_names().contains(&unit) {
+                    bail!("{unit} is not one of the units of app {}", self.name);
+                }
+                Ok(unit)
+            }
+            None => Ok(format!("{}.service", self.name)),
+        }
+    }
+
+    // streams the entries in journald's export format, which
+    // `systemd-journal-remote` can import elsewhere
+    pub fn export_journal_to_file(&self, path: &path::Path, opts: &LogsOptions) -> Result<()> {
+        let service_name = format!("{}.service", self.name);
+        let mut cmd = process::Command::new(&self.config.systemd.journalctl_bin.0);
+        cmd.args(["-u", &service_name, "--output", "export", "--no-pager"]);
+        cmd.args(self.journal_namespace_arg());
+        if let Some(since) = &opts.since {
+            cmd.args(["--since", since]);
+        }
+        if let Some(until) = &opts.until {
+            cmd.args(["--until", until]);
+        }
+        let mut child = cmd
+            .stdout(process::Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.config.systemd.journalctl_bin.0))?;
+        let mut stdout = child.stdout.take().unwrap();
+
+        let file =
+            fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()));
+        let written = file.map(|file| {
+            let written = if path.extension().is_some_and(|e| e == "gz") {
+                let mut encoder = GzEncoder::new(file, Compression::default());
+                io::copy(&mut stdout, &mut encoder).and_then(|_| encoder.finish().map(|_| ()))
+            } else {
+                let mut file = file;
+                io::copy(&mut stdout, &mut file).map(|_| ())
+            };
+            written.with_context(|| format!("Failed to write {}", path.display()))
+        });
+
+        // closing the pipe makes journalctl exit instead of blocking on a full one
+        drop(stdout);
+        let status = child.wait().with_context(|| {
+            format!(
+                "Failed to wait for {}",
+                self.config.systemd.journalctl_bin.0
+            )
+        });
+
+        // a file that was never created has nothing to clean up
+        let written = written?;
+        let result = written.and_then(|()| {
+            if !status?.success() {
+                bail!("Failed to export logs for '{}'", self.name);
+            }
+            Ok(())
+        });
+        if result.is_err() {
+            // don't leave a truncated export behind
+            let _ = fs::remove_file(path);
+        }
+        result
+    }
+
+    // archives the active journal files, then drops every archived entry
+    pub fn rotate_logs(&self) -> Result<()> {
+        self.journalctl(&["--rotate"], "rotate logs")?;
+        self.vacuum_logs(None, Some("1s"))
+    }
+
+    // journald vacuums whole journal files, -u has no effect, so this only
+    // stays within the app with a log namespace
+    pub fn vacuum_logs(&self, max_size: Option<&str>, max_time: Option<&str>) -> Result<()> {
+        let mut args = Vec::new();
+        if let Some(max_size) = max_size {
+            args.push(format!("--vacuum-size={max_size}"));
+        }
+        if let Some(max_time) = max_time {
+            args.push(format!("--vacuum-time={max_time}"));
+        }
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.journalctl(&args, "vacuum logs")
+    }
+
+    fn journalctl(&self, args: &[&str], action: &str) -> Result<()> {
+        let mut args = args.to_vec();
+        let namespace = self.journal_namespace_arg();
+        args.extend(namespace.as_deref());
+        let output = self
+            .runner
+            .run(&self.config.systemd.journalctl_bin.0, &args)
+            .with_context(|| format!("Failed to {action} for '{}'", self.name))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to {action} for '{}': {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        // the vacuum reports what it deleted on stderr
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            logging::info(line);
+        }
+        Ok(())
+    }
+
+    // --disk-usage ignores -u, so only a log namespace has a size of its own
+    pub fn get_journal_size(&self) -> Result<Option<u64>> {
+        let Some(namespace) = self.journal_namespace_arg() else {
+            return Ok(None);
+        };
+        let args = ["--disk-usage", &namespace];
+        let output = self
+            .runner
+            .run(&self.config.systemd.journalctl_bin.0, &args)
+            .with_context(|| format!("Failed to get the journal size for '{}'", self.name))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to get the journal size for '{}': {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_disk_usage(&stdout)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Failed to parse journal size '{}'", stdout.trim()))
+    }
+
+    pub fn get_unit_files_size(&self) -> Result<u64> {
+        let mut size = 0;
+        for file in self.target_paths()? {
+            if let Ok(metadata) = fs::metadata(&file) {
+                size += metadata.len();
+            }
+        }
+        Ok(size)
+    }
+
+    // what install would copy, as opposed to the installed files in get_unit_files_size
+    pub fn compute_install_footprint(&self) -> Result<u64> {
+        Ok(self
+            .install_file_sizes()?
+            .iter()
+            .map(|(_, size)| size)
+            .sum())
+    }
+
+    pub fn install_file_sizes(&self) -> Result<Vec<(path::PathBuf, u64)>> {
+        let mut sizes = Vec::new();
+        for file in self.get_app_files()? {
+            let size = fs::metadata(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?
+                .len();
+            sizes.push((file, size));
+        }
+        Ok(sizes)
+    }
+
+    pub fn get_log_priority_stats(&self, since: Option<&str>) -> Result<HashMap<String, u32>> {
+        let service_name = format!("{}.service", self.name);
+        let mut args = vec![
+            "-u",
+            &service_name,
+            "--output",
+            "json",
+            "-p",
+            "0..7",
+            "--no-pager",
+        ];
+        let namespace = self.journal_namespace_arg();
+        args.extend(namespace.as_deref());
+        if let Some(since) = since {
+            args.extend(["--since", since]);
+        }
+
+        let output = self
+            .runner
+            .run(&self.config.systemd.journalctl_bin.0, &args)
+            .with_context(|| format!("Failed to read logs for '{}'", self.name))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to read logs for '{}': {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut stats = HashMap::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let Ok(entry) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            let priority = entry["PRIORITY"]
+                .as_str()
+                .and_then(|p| p.parse::<usize>().ok())
+                .and_then(|p| LOG_PRIORITIES.get(p));
+            if let Some(priority) = priority {
+                *stats.entry(priority.to_string()).or_insert(0) += 1;
+            }
+        }
+
+        Ok(stats)
+    }
+
+    pub fn daemon_reload(&self) -> Result<()> {
+        let args = self.prepare_systemctl_args(vec![String::from("daemon-reload")]);
+        if !self
+            .systemctl(args)
+            .context("Failed to reload systemd")?
+            .success()
+        {
+            bail!("Failed to reload systemd");
+        }
+
+        Ok(())
+    }
+
+    pub fn generate_unit_file(&self) -> Result<String> {
+        let service = &self.config.service;
+        let exec_start = service
+            .exec_start
+            .as_ref()
+            .ok_or_else(|| anyhow!("No service.exec_start configured for app {}", self.name))?;
+
+        let mut unit = format!("[Unit]\nDescription={}\n\n[Service]\n", self.name);
+        unit.push_str(&format!("ExecStart={exec_start}\n"));
+        if let Some(user) = &service.user {
+            unit.push_str(&format!("User={user}\n"));
+        }
+        if let Some(working_directory) = &service.working_directory {
+            unit.push_str(&format!("WorkingDirectory={working_directory}\n"));
+        }
+        if let Some(restart) = &service.restart {
+            unit.push_str(&format!("Restart={restart}\n"));
+        }
+
+        // user managers have no multi-user.target
+        let default_target = if self.use_user {
+            "default.target"
+        } else {
+            "multi-user.target"
+        };
+        let wanted_by = service.wanted_by.as_deref().unwrap_or(default_target);
+        unit.push_str(&format!("\n[Install]\nWantedBy={wanted_by}\n"));
+
+        Ok(unit)
+    }
+
+    pub fn service_type(&self) -> Result<String> {
+        // generated services, e.g. from quadlet .container files, have no source file
+        let service_type = match self.service_file()? {
+            Some(file) => unit_file::get_value(&fs::read_to_string(file)?, "Service", "Type"),
+            None => None,
+        };
+        Ok(service_type.unwrap_or_else(|| String::from("simple")))
+    }
+
+    pub fn get_exec_start(&self) -> Result<Option<String>> {
+        let Some(file) = self.service_file()? else {
+            return Ok(None);
+        };
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+
+        // an empty ExecStart= only resets the list
+        Ok(unit_file::get_value(&content, "Service", "ExecStart").filter(|v| !v.is_empty()))
+    }
+
+    pub fn wait_for_active(&self, timeout: time::Duration) -> Result<()> {
+        let service_name = format!("{}.service", self.name);
+        let started = time::Instant::now();
+
+        while started.elapsed() < timeout {
+            if systemd::is_active(&service_name, !self.use_user, self.runner.as_ref())? {
+                return Ok(());
+            }
+            if systemd::is_failed(&service_name, !self.use_user, self.runner.as_ref())? {
+                bail!("{service_name} failed to start");
+            }
+            thread::sleep(time::Duration::from_millis(500));
+        }
+
+        bail!(
+            "{service_name} did not become active within {}s",
+            timeout.as_secs()
+        )
+    }
+
+    pub fn get_active_since(&self) -> Result<Option<time::SystemTime>> {
+        if self.show_property("ActiveState")? != "active" {
+            return Ok(None);
+        }
+
+        let timestamp = self.show_property("ActiveEnterTimestamp")?;
+        let since = timestamp::parse_systemd(&timestamp)
+            .ok_or_else(|| anyhow!("Failed to parse timestamp '{timestamp}'"))?;
+        Ok(Some(since))
+    }
+
+    pub fn get_memory_usage(&self) -> Result<Option<u64>> {
+        // reported as "[not set]" or "infinity" when accounting is unavailable
+        if let Ok(bytes) = self.show_property("MemoryCurrent")?.parse() {
+            return Ok(Some(bytes));
+        }
+
+        // fall back to reading the cgroup directly
+        let Some(cgroup_path) = self.get_cgroup_path()? else {
+            return Ok(None);
+        };
+        let memory_file = path::Path::new("/sys/fs/cgroup")
+            .join(cgroup_path.trim_start_matches('/'))
+            .join("memory.current");
+        Ok(fs::read_to_string(memory_file)
+            .ok()
+            .and_then(|content| content.trim().parse().ok()))
+    }
+
+    // None when task accounting is off, systemd then reports u64::MAX or "[not set]"
+    pub fn get_tasks_current(&self) -> Result<Option<u32>> {
+        let tasks = self.show_property("TasksCurrent")?;
+        Ok(tasks.parse().ok())
+    }
+
+    // None when there's no limit
+    pub fn get_tasks_max(&self) -> Result<Option<u32>> {
+        let tasks = self.show_property("TasksMax")?;
+        Ok(tasks.parse().ok())
+    }
+
+    // sampled over 100ms, so it can go above 100% when several cores are busy
+    pub fn get_cpu_usage_percent(&self) -> Result<Option<f64>> {
+        let Some(cgroup_path) = self.get_cgroup_path()? else {
+            return Ok(None);
+        };
+        // only cgroup v2 reports usage_usec in cpu.stat
+        let stat_file = path::Path::new("/sys/fs/cgroup")
+            .join(cgroup_path.trim_start_matches('/'))
+            .join("cpu.stat");
+        let Some(first) = read_cpu_usage_usec(&stat_file) else {
+            return Ok(None);
+        };
+
+        let started = time::Instant::now();
+        thread::sleep(CPU_SAMPLE_INTERVAL);
+        let Some(second) = read_cpu_usage_usec(&stat_file) else {
+            return Ok(None);
+        };
+        let elapsed = started.elapsed().as_micros() as f64;
+        Ok(Some(second.saturating_sub(first) as f64 / elapsed * 100.0))
+    }
+
+    pub fn get_environment(&self) -> Result<Vec<String>> {
+        let environment = self.show_property("Environment")?;
+        Ok(unit_file::split_quoted(&environment))
+    }
+
+    // None when the service has no watchdog
+    pub fn get_watchdog_usec(&self) -> Result<Option<u64>> {
+        let watchdog = self.show_property("WatchdogUSec")?;
+        Ok(timestamp::parse_timespan(&watchdog)
+            .filter(|d| !d.is_zero())
+            .map(|d| d.as_micros() as u64))
+    }
+
+    // whole watchdog intervals since the last ping, None if it never pinged
+    pub fn get_watchdog_missed_count(&self) -> Result<Option<u64>> {
+        let Some(watchdog_usec) = self.get_watchdog_usec()? else {
+            return Ok(None);
+        };
+        let last_ping = self.show_property("WatchdogTimestamp")?;
+        let Some(last_ping) = timestamp::parse_systemd(&last_ping) else {
+            return Ok(None);
+        };
+
+        let elapsed = time::SystemTime::now()
+            .duration_since(last_ping)
+            .unwrap_or_default();
+        Ok(Some(elapsed.as_micros() as u64 / watchdog_usec))
+    }
+
+    // None when the service hasn't been started since boot
+    pub fn get_invocation_id(&self) -> Result<Option<String>> {
+        let invocation_id = self.show_property("InvocationID")?;
+        Ok(Some(invocation_id).filter(|id| !id.is_empty()))
+    }
+
+    // 0 when the service doesn't keep a file descriptor store
+    pub fn get_file_descriptor_store_max(&self) -> Result<u32> {
+        let max = self.show_property("FileDescriptorStoreMax")?;
+        max.parse().with_context(|| {
+            format!(
+                "Failed to parse FileDescriptorStoreMax '{max}' for {}",
+                self.name
+            )
+        })
+    }
+
+    // None when the service doesn't keep a file descriptor store
+    pub fn get_file_descriptor_count(&self) -> Result<Option<u32>> {
+        if self.get_file_descriptor_store_max()? == 0 {
+            return Ok(None);
+        }
+        let count = self.show_property("NFileDescriptorStore")?;
+        count.parse().map(Some).with_context(|| {
+            format!(
+                "Failed to parse NFileDescriptorStore '{count}' for {}",
+                self.name
+            )
+        })
+    }
+
+    pub fn get_restart_count(&self) -> Result<u32> {
+        let restarts = self.show_property("NRestarts")?;
+        restarts
+            .parse()
+            .with_context(|| format!("Failed to parse NRestarts '{restarts}' for {}", self.name))
+    }
+
+    pub fn get_cgroup_path(&self) -> Result<Option<String>> {
+        // empty when the service isn't running
+        let cgroup_path = self.show_property("ControlGroup")?;
+        if cgroup_path.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(cgroup_path))
+    }
+
+    pub fn install_directory(&self) -> Result<()> {
+        if !self.systemd_dir.exists() {
+            if !self.config.systemd.create_install_dir {
+                bail!(
+                    "Install location {} does not exist and create_install_dir is disabled",
+                    self.systemd_dir.display()
+                );
+            }
+            fs::create_dir_all(&self.systemd_dir).with_context(|| {
+                format!(
+                    "Failed to create install location {}",
+                    self.systemd_dir.display()
+                )
+            })?;
+            logging::info(&format!("Created directory {}", self.systemd_dir.display()));
+        }
+
+        // mode bits can't account for supplementary groups, ACLs or read-only
+        // mounts, so actually try to write
+        let probe = self
+            .systemd_dir
+            .join(format!(".units-write-test-{}", process::id()));
+        match fs::File::create_new(&probe) {
+            Ok(_) => fs::remove_file(&probe)
+                .with_context(|| format!("Failed to remove {}", probe.display())),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => bail!(
+                "Install location {} is not writable, try running with sudo",
+                self.systemd_dir.display()
+            ),
+            Err(e) => Err(e).with_context(|| {
+                format!(
+                    "Install location {} is not writable",
+                    self.systemd_dir.display()
+                )
+            }),
+        }
+    }
+
+    pub fn create_service_account(&self) -> Result<()> {
+        let user = self.service_user();
+        if self.runner.run("id", &[user])?.status.success() {
+            return Ok(());
+        }
+
+        let output = self.runner.run(
+            "useradd",
+            &[
+                "--system",
+                "--no-create-home",
+                "--shell",
+                "/usr/sbin/nologin",
+                user,
+            ],
+        )?;
+        if !output.status.success() {
+            bail!(
+                "Failed to create service user {user}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        logging::info(&format!("Created service user {user}"));
+
+        Ok(())
+    }
+
+    pub fn delete_service_account(&self) -> Result<()> {
+        let user = self.service_user();
+        let output = self.runner.run("userdel", &[user])?;
+        if !output.status.success() {
+            bail!(
+                "Failed to delete service user {user}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        logging::info(&format!("Deleted service user {user}"));
+
+        Ok(())
+    }
+
+    // writes the app's tmpfiles.d config and creates what it lists right away,
+    // systemd-tmpfiles recreates them on boot
+    pub fn create_tmpfiles_d(&self) -> Result<()> {
+        let Some(content) = self.tmpfiles_d_config() else {
+            return Ok(());
+        };
+
+        let tmpfiles_path = self.tmpfiles_d_path()?;
+        if let Some(parent) = tmpfiles_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&tmpfiles_path, content)
+            .with_context(|| format!("Failed to write {}", tmpfiles_path.display()))?;
+        logging::info(&format!("Wrote {}", tmpfiles_path.display()));
+
+        self.systemd_tmpfiles("--create", &tmpfiles_path)
+    }
+
+    pub fn remove_tmpfiles_d(&self) -> Result<()> {
+        let tmpfiles_path = self.tmpfiles_d_path()?;
+        if !tmpfiles_path.exists() {
+            return Ok(());
+        }
+
+        // systemd-tmpfiles looks the config up by name, so it has to run first
+        self.systemd_tmpfiles("--remove", &tmpfiles_path)?;
+        fs::remove_file(&tmpfiles_path)
+            .with_context(|| format!("Failed to remove {}", tmpfiles_path.display()))?;
+        logging::info(&format!("Removed file {}", tmpfiles_path.display()));
+
+        Ok(())
+    }
+
+    // systemd-sysusers only creates users and groups that don't exist yet
+    pub fn create_sysusers_d(&self) -> Result<()> {
+        let Some(content) = self.sysusers_d_config() else {
+            return Ok(());
+        };
+
+        let sysusers_path = self.sysusers_d_path();
+        fs::create_dir_all(SYSUSERS_DIR)
+            .with_context(|| format!("Failed to create {SYSUSERS_DIR}"))?;
+        fs::write(&sysusers_path, content)
+            .with_context(|| format!("Failed to write {}", sysusers_path.display()))?;
+        logging::info(&format!("Wrote {}", sysusers_path.display()));
+
+        let file_name = format!("{}.conf", self.name);
+        let output = self.runner.run("systemd-sysusers", &[&file_name])?;
+        if !output.status.success() {
+            bail!(
+                "systemd-sysusers {file_name} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    // the users and groups stay, files owned by them would be orphaned otherwise
+    pub fn remove_sysusers_d(&self) -> Result<()> {
+        let sysusers_path = self.sysusers_d_path();
+        if !sysusers_path.exists() {
+            return Ok(());
+        }
+
+        fs::remove_file(&sysusers_path)
+            .with_context(|| format!("Failed to remove {}", sysusers_path.display()))?;
+        logging::info(&format!("Removed file {}", sysusers_path.display()));
+        Ok(())
+    }
+
+    // copied files keep the context of the app directory, which systemd may not be
+    // allowed to read
+    pub fn apply_selinux_context(&self) -> Result<()> {
+        if !self.restore_selinux_context() {
+            return Ok(());
+        }
+
+        for target_path in self.target_paths()? {
+            let target = target_path.to_string_lossy();
+            let output = self.runner.run("restorecon", &["-v", &target])?;
+            if !output.status.success() {
+                bail!(
+                    "Failed to restore the SELinux context of {target}: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            // -v reports each relabelled file
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                logging::info(line.trim());
+            }
+        }
+        Ok(())
+    }
+
+    // defaults to the app name
+    pub fn service_user(&self) -> &str {
+        self.config
+            .systemd
+            .service_user
+            .as_deref()
+            .unwrap_or(&self.name)
+    }
+
+    // the drop-ins install writes, in order, with None for the ones this
+    // config doesn't need
+    fn drop_ins(&self, env: &[String]) -> Result<Vec<(&'static str, Option<String>)>> {
+        Ok(vec![
+            (ENV_FILE_NAME, env_drop_in(env)?),
+            (WATCHDOG_FILE_NAME, self.watchdog_drop_in()),
+            (RESOURCE_LIMITS_FILE_NAME, self.resource_limits_drop_in()),
+            // the service then logs to its own journal, read with --namespace
+            (LOG_NAMESPACE_FILE_NAME, self.log_namespace_drop_in()),
+            (ORDERING_FILE_NAME, self.ordering_drop_in()),
+            (BINDING_FILE_NAME, self.binding_drop_in()),
+            (LOGGING_FILE_NAME, self.logging_drop_in()),
+            (START_LIMIT_FILE_NAME, self.start_limit_drop_in()),
+            // lets socket activated services hand their fds to systemd across restarts
+            (
+                FD_STORE_FILE_NAME,
+                self.config
+                    .systemd
+                    .fd_store
+                    .then(|| String::from(FD_STORE_DROP_IN)),
+            ),
+        ])
+    }
+
+    pub fn log_namespace(&self) -> Option<&str> {
+        self.config.systemd.log_namespace_id.as_deref()
+    }
+
+    pub fn verify_unit_syntax(&self, unit_file: &path::Path) -> Result<Vec<Warning>> {
+        let content = fs::read_to_string(unit_file)
+            .with_context(|| format!("Failed to read {}", unit_file.display()))?;
+        let unit_section = match unit_file.extension().and_then(|e| e.to_str()) {
+            Some("timer") => "Timer",
+            Some("socket") => "Socket",
+            _ => "Service",
+        };
+
+        let mut warnings = Vec::new();
+        let mut sections = Vec::new();
+        let mut section = String::new();
+        let mut wanted_by = false;
+        let mut exec_start = false;
+        for (line, content) in unit_file::logical_lines(&content) {
+            if content.starts_with('[') && content.ends_with(']') {
+                section = content[1..content.len() - 1].to_string();
+                sections.push(section.clone());
+                continue;
+            }
+
+            let Some((key, value)) = content.split_once('=') else {
+                warnings.push(Warning {
+                    line,
+                    message: format!("Expected Key=Value, found '{content}'"),
+                });
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            match (section.as_str(), key) {
+                ("Install", "WantedBy") => wanted_by = true,
+                ("Service", "ExecStart") => {
+                    exec_start = true;
+                    if value.is_empty() {
+                        warnings.push(Warning {
+                            line,
+                            message: String::from("ExecStart= is empty"),
+                        });
+                    }
+                }
+                _ => {}
+            }
+            if let Some(message) = deprecated_directive(key, value) {
+                warnings.push(Warning { line, message });
+            }
+        }
+
+        // a socket activated service is pulled in by its .socket and needs no [Install]
+        let socket_activated = unit_section == "Service"
+            && unit_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .is_some_and(|stem| {
+                    unit_file
+                        .with_file_name(format!("{}.socket", stem.trim_end_matches('@')))
+                        .exists()
+                });
+        for required in ["Unit", unit_section, "Install"] {
+            if required == "Install" && socket_activated {
+                continue;
+            }
+            if !sections.iter().any(|s| s == required) {
+                warnings.push(Warning {
+                    line: 0,
+                    message: format!("Missing [{required}] section"),
+                });
+            }
+        }
+        if sections.iter().any(|s| s == "Install") && !wanted_by {
+            warnings.push(Warning {
+                line: 0,
+                message: String::from("Missing WantedBy= in [Install]"),
+            });
+        }
+        if unit_section == "Service" && !exec_start {
+            warnings.push(Warning {
+                line: 0,
+                message: String::from("Missing ExecStart= in [Service]"),
+            });
+        }
+
+        Ok(warnings)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let socket_activated = self
+            .get_app_files()?
+            .iter()
+            .any(|file| file.extension() == Some(ffi::OsStr::new("socket")));
+        if socket_activated && !self.config.systemd.fd_store {
+            logging::warn(&format!(
+                "{} uses socket activation without fd_store, open sockets will not survive restarts",
+                self.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn enable_watchdog(&self) -> Result<thread::JoinHandle<()>> {
+        if !self.config.systemd.watchdog_enabled {
+            bail!("Watchdog is not enabled for app {}", self.name);
+        }
+        if env::var_os("NOTIFY_SOCKET").is_none() {
+            bail!(
+                "NOTIFY_SOCKET is not set, watchdog pings can only be sent from within a service"
+            );
+        }
+
+        let watchdog = self.show_property("WatchdogUSec")?;
+        let interval = timestamp::parse_timespan(&watchdog)
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| anyhow!("No watchdog configured for {}.service", self.name))?;
+
+        let name = self.name.clone();
+        let handle = thread::spawn(move || {
+            loop {
+                if let Err(e) = sd_notify::notify(&[sd_notify::NotifyState::Watchdog]) {
+                    logging::warn(&format!("Failed to send watchdog ping for {name}: {e}"));
+                }
+                thread::sleep(interval / 2);
+            }
+        });
+
+        Ok(handle)
+    }
+
+    // for shims around services that don't speak sd_notify, the unit needs
+    // NotifyAccess=all for systemd to accept it from a child process
+    pub fn notify_systemd_ready(&self) -> Result<()> {
+        let notify_socket = env::var("NOTIFY_SOCKET").map_err(|_| {
+            anyhow!("NOTIFY_SOCKET is not set, readiness can only be sent from within a service")
+        })?;
+
+        // a leading @ is a socket in the abstract namespace
+        let address = match notify_socket.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+            None => SocketAddr::from_pathname(&notify_socket),
+        }
+        .with_context(|| format!("Invalid NOTIFY_SOCKET '{notify_socket}'"))?;
+
+        let socket = UnixDatagram::unbound().context("Failed to create notify socket")?;
+        socket
+            .send_to_addr(b"READY=1\n", &address)
+            .with_context(|| format!("Failed to notify systemd that {} is ready", self.name))?;
+
+        Ok(())
+    }
+
+    pub fn detect_stale_unit(&self) -> Result<bool> {
+        Ok(self.show_property("NeedDaemonReload")? == "yes")
+    }
+
+    pub fn restart(&self) -> Result<()> {
+        let mut units = self.unit_names();
+        // restart_mode only applies to the main service
+        if self.config.systemd.restart_mode == RestartMode::StopStart {
+            self.stop_then_start(false)?;
+            units.remove(0);
+        }
+
+        for unit in units {
+            let args = self.prepare_systemctl_args(vec![String::from("restart"), unit.clone()]);
+            let status = self.systemctl(args)?;
+
+            if !status.success() {
+                bail!("Failed to restart {unit}");
+            }
+        }
+
+        Ok(())
+    }
+
+    // for services that don't survive `systemctl restart`, waits for the
+    // service to be fully stopped before starting it again
+    pub fn stop_then_start(&self, dry_run: bool) -> Result<()> {
+        let service_name = format!("{}.service", self.name);
+        if dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would stop {service_name}, wait for it to stop and start it again"
+            ));
+            return Ok(());
+        }
+
+        let args = self.prepare_systemctl_args(vec![String::from("stop"), service_name.clone()]);
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to stop {service_name}");
+        }
+
+        let timeout = self
+            .config
+            .systemd
+            .stop_timeout_secs
+            .map(|secs| time::Duration::from_secs(secs.into()))
+            .unwrap_or(ACTIVE_TIMEOUT);
+        let started = time::Instant::now();
+        while systemd::is_active(&service_name, !self.use_user, self.runner.as_ref())? {
+            if started.elapsed() >= timeout {
+                bail!("{service_name} did not stop within {}s", timeout.as_secs());
+            }
+            thread::sleep(time::Duration::from_millis(500));
+        }
+
+        let args = self.prepare_systemctl_args(vec![String::from("start"), service_name.clone()]);
+        if !self.systemctl(args)?.success() {
+            bail!("Failed to start {service_name}");
+        }
+
+        Ok(())
+    }
+
+    pub fn create_override(&self, section: &str, property: &str, value: &str) -> Result<()> {
+        let drop_in_dir = self.drop_in_dir();
+        fs::create_dir_all(&drop_in_dir)
+            .with_context(|| format!("Failed to create {}", drop_in_dir.display()))?;
+
+        let override_path = drop_in_dir.join(OVERRIDE_FILE_NAME);
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&override_path)
+            .with_context(|| format!("Failed to open {}", override_path.display()))?;
+
+        // separate appended overrides from existing ones
+        let separator = if file.metadata()?.len() > 0 { "\n" } else { "" };
+        write!(file, "{separator}[{section}]\n{property}={value}\n")
+            .with_context(|| format!("Failed to write {}", override_path.display()))?;
+        logging::info(&format!(
+            "Added {property}={value} to {}",
+            override_path.display()
+        ));
+
+        self.daemon_reload()
+    }
+
+    pub fn list_drop_ins(&self) -> Result<Vec<path::PathBuf>> {
+        let drop_in_dir = self.drop_in_dir();
+        if !drop_in_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut files = Vec::new();
+        for entry in WalkDir::new(&drop_in_dir).min_depth(1).max_depth(1) {
+            let path = entry?.into_path();
+            if path.is_file() && path.extension() == Some(ffi::OsStr::new("conf")) {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        Ok(files)
+    }
+
+    pub fn remove_override(&self) -> Result<()> {
+        let override_path = self.override_path();
+        if !override_path.exists() {
+            bail!("No override found for app {}", self.name)
+        }
+
+        fs::remove_file(&override_path)
+            .with_context(|| format!("Failed to remove {}", override_path.display()))?;
+        logging::info(&format!("Removed file {}", override_path.display()));
+
+        self.daemon_reload()
+    }
+
+    fn service_file(&self) -> Result<Option<path::PathBuf>> {
+        let file_name = format!("{}.service", self.name);
+        let file = self
+            .get_app_files()?
+            .into_iter()
+            .find(|f| f.file_name() == Some(ffi::OsStr::new(&file_name)));
+        Ok(file)
+    }
+
+    // unified diff from the installed files to the app's source files
+    pub fn diff_installed(&self, context: usize) -> Result<String> {
+        let mut out = String::new();
+        for file in self.get_app_files()? {
+            let target = self.systemd_dir.join(file.strip_prefix(&self.app_dir)?);
+            let source = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let (installed, installed_name) = match fs::read_to_string(&target) {
+                Ok(content) => (content, target.display().to_string()),
+                Err(_) => (String::new(), String::from("/dev/null")),
+            };
+
+            let diff = similar::TextDiff::from_lines(&installed, &source);
+            let unified = diff
+                .unified_diff()
+                .context_radius(context)
+                .header(&installed_name, &file.display().to_string())
+                .to_string();
+            out.push_str(&unified);
+        }
+        Ok(out)
+    }
+
+    pub fn target_paths(&self) -> Result<Vec<path::PathBuf>> {
+        let mut targets = Vec::new();
+        for file in self.get_app_files()? {
+            let unit_name = file.strip_prefix(&self.app_dir)?;
+            targets.push(self.systemd_dir.join(unit_name));
+        }
+
+        Ok(targets)
+    }
+
+    // the installed unit files and every drop-in, for backups
+    pub fn installed_files(&self) -> Result<Vec<path::PathBuf>> {
+        let mut files: Vec<path::PathBuf> = self
+            .target_paths()?
+            .into_iter()
+            .filter(|f| f.exists())
+            .collect();
+        let drop_in_dir = self.drop_in_dir();
+        if drop_in_dir.is_dir() {
+            for entry in WalkDir::new(&drop_in_dir).sort_by_file_name() {
+                let entry = entry?;
+                if entry.file_type().is_file() {
+                    files.push(entry.into_path());
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    pub fn installed_file_mtimes(&self) -> Result<HashMap<path::PathBuf, time::SystemTime>> {
+        let mut mtimes = HashMap::new();
+        for target in self.target_paths()? {
+            let Ok(metadata) = fs::metadata(&target) else {
+                continue;
+            };
+            mtimes.insert(target, metadata.modified()?);
+        }
+        Ok(mtimes)
+    }
+
+    // whether a source file was edited after the install that recorded
+    // `install_mtimes`, or added since
+    pub fn source_changed_since_install(
+        &self,
+        install_mtimes: &HashMap<path::PathBuf, time::SystemTime>,
+    ) -> Result<bool> {
+        for file in self.get_app_files()? {
+            let target = self.systemd_dir.join(file.strip_prefix(&self.app_dir)?);
+            let Some(installed) = install_mtimes.get(&target) else {
+                return Ok(true);
+            };
+            let modified = fs::metadata(&file)
+                .and_then(|m| m.modified())
+                .with_context(|| format!("Failed to read the mtime of {}", file.display()))?;
+            // the recorded mtimes only keep whole seconds
+            let secs = |t: &time::SystemTime| {
+                t.duration_since(time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+            };
+            if secs(&modified) > secs(installed) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // the --env variables of the last install, read back from its drop-in
+    pub fn installed_env(&self) -> Result<Vec<String>> {
+        let env_path = self.drop_in_dir().join(ENV_FILE_NAME);
+        if !env_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = fs::read_to_string(&env_path)
+            .with_context(|| format!("Failed to read {}", env_path.display()))?;
+        Ok(content
+            .lines()
+            .filter_map(|line| line.strip_prefix("Environment="))
+            .flat_map(unit_file::split_quoted)
+            .collect())
+    }
+
+    pub fn get_target_paths(&self) -> Result<HashSet<path::PathBuf>> {
+        Ok(self.target_paths()?.into_iter().collect())
+    }
+
+    // names of the other apps that would install over one of this app's files
+    pub fn detect_conflicting_apps(&self, others: &[App]) -> Result<Vec<String>> {
+        let targets = self.get_target_paths()?;
+        let mut conflicting = Vec::new();
+        for other in others.iter().filter(|o| o.name != self.name) {
+            if other
+                .get_target_paths()?
+                .intersection(&targets)
+                .next()
+                .is_some()
+            {
+                conflicting.push(other.name.clone());
+            }
+        }
+        Ok(conflicting)
+    }
+
+    fn show_property(&self, property: &str) -> Result<String> {
+        self.show_unit_property(&format!("{}.service", self.name), property)
+    }
+
+    fn show_unit_property(&self, unit: &str, property: &str) -> Result<String> {
+        let args = self.prepare_systemctl_args(vec![
+            String::from("show"),
+            format!("--property={property}"),
+            String::from("--value"),
+            unit.to_string(),
+        ]);
+        // timestamps are formatted in the local timezone, so pin it to UTC
+        let mut env_args = vec!["TZ=UTC", "systemctl"];
+        env_args.extend(args.iter().map(String::as_str));
+        let output = self
+            .runner
+            .run("env", &env_args)
+            .with_context(|| format!("Failed to read {property} for {}", self.name))?;
+
+        if !output.status.success() {
+            bail!(
+                "Failed to read {property} for {}: {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    }
+
+    pub fn test_configuration(&self) -> Result<Vec<VerifyReport>> {
+        let mut reports = Vec::new();
+        for file in self.get_app_files()? {
+            let is_unit = matches!(
+                file.extension().and_then(|e| e.to_str()),
+                Some("service" | "timer" | "socket" | "path" | "mount" | "target")
+            );
+            if is_unit {
+                reports.push(self.analyze_verify(file)?);
+            }
+        }
+
+        Ok(reports)
+    }
+
+    // verifies the installed service, which picks up its drop-ins too
+    pub fn verify_installed_service(&self) -> Result<VerifyReport> {
+        self.analyze_verify(self.systemd_dir.join(format!("{}.service", self.name)))
+    }
+
+    pub fn override_path(&self) -> path::PathBuf {
+        self.drop_in_dir().join(OVERRIDE_FILE_NAME)
+    }
+
+    // the existing override, or a template listing the unit file to start from
+    pub fn generate_override_file(&self) -> Result<String> {
+        let override_path = self.override_path();
+        if override_path.exists() {
+            return fs::read_to_string(&override_path)
+                .with_context(|| format!("Failed to read {}", override_path.display()));
+        }
+
+        let mut content = format!(
+            "# Override for {}.service, settings below are applied on top of the unit file\n",
+            self.name
+        );
+        if let Some(file) = self.service_file()? {
+            content.push_str(&format!("#\n# {}:\n", file.display()));
+            for line in fs::read_to_string(&file)?.lines() {
+                content.push_str(format!("# {line}").trim_end());
+                content.push('\n');
+            }
+        }
+        content.push_str("\n[Service]\n");
+        Ok(content)
+    }
+
+    fn analyze_verify(&self, file: path::PathBuf) -> Result<VerifyReport> {
+        let path = file.to_string_lossy().to_string();
+        let mut args = vec!["verify", path.as_str()];
+        if self.use_user {
+            args.insert(0, "--user");
+        }
+        let output = self
+            .runner
+            .run(&self.config.systemd.analyze_bin.0, &args)
+            .with_context(|| format!("Failed to run {}", self.config.systemd.analyze_bin.0))?;
+
+        // systemd-analyze only fails on errors, anything it prints otherwise is a warning
+        let mut messages: Vec<String> = String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .chain(String::from_utf8_lossy(&output.stdout).lines())
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect();
+        let mut report = VerifyReport {
+            unit: file,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        };
+        if output.status.success() {
+            report.warnings = messages;
+        } else {
+            if messages.is_empty() {
+                messages.push(format!(
+                    "systemd-analyze verify exited with {}",
+                    output.status
+                ));
+            }
+            report.errors = messages;
+        }
+        Ok(report)
+    }
+
+    fn analyze_security(&self, json: bool) -> Result<Vec<u8>> {
+        let service_name = format!("{}.service", self.name);
+        let mut args = vec![String::from("security"), service_name];
+        if json {
+            args.push(String::from("--json=short"));
+        }
+
+        let args = self.prepare_systemctl_args(args);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self
+            .runner
+            .run(&self.config.systemd.analyze_bin.0, &args)
+            .with_context(|| format!("Failed to run {}", self.config.systemd.analyze_bin.0))?;
+
+        if !output.status.success() {
+            bail!(
+                "Failed to analyze security for {}: {}",
+                self.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn drop_in_dir(&self) -> path::PathBuf {
+        self.systemd_dir.join(format!("{}.service.d", self.name))
+    }
+
+    // copies every file into a staging directory next to the install location
+    // first, so the final renames are the only step that can leave a partial
+    // install behind, and those are rolled back on failure
+    pub fn install_atomically(&self, dry_run: bool, overwrite: bool) -> Result<()> {
+        let staging_dir = self
+            .systemd_dir
+            .join(format!(".units-staging-{}", self.name));
+        let app_files = self.get_app_files()?;
+
+        if dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would stage {} files in {} and move them into place",
+                app_files.len(),
+                staging_dir.display()
+            ));
+            return Ok(());
+        }
+
+        // left over from an install that was interrupted
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)
+                .with_context(|| format!("Failed to remove {}", staging_dir.display()))?;
+        }
+
+        let mut staged = Vec::new();
+        for file in &app_files {
+            let unit_name = file.strip_prefix(&self.app_dir)?;
+            let staged_path = staging_dir.join("new").join(unit_name);
+            self.copy_with_permissions(file, &staged_path)?;
+            if fs::read(file)? != fs::read(&staged_path)? {
+                fs::remove_dir_all(&staging_dir)?;
+                bail!(
+                    "Staged copy of {} doesn't match the original",
+                    file.display()
+                );
+            }
+            staged.push((
+                staged_path,
+                staging_dir.join("old").join(unit_name),
+                self.systemd_dir.join(unit_name),
+            ));
+        }
+
+        for (i, (staged_path, backup_path, target_path)) in staged.iter().enumerate() {
+            let result = move_into_place(staged_path, backup_path, target_path, overwrite);
+            if let Err(e) = result {
+                logging::warn(&format!("Install of {} failed, rolling back", self.name));
+                let mut restored = true;
+                for (staged_path, backup_path, target_path) in staged[..i].iter().rev() {
+                    restored &= roll_back(staged_path, backup_path, target_path);
+                }
+                // file i's original is still aside if move_into_place
+                // couldn't put it back
+                restored &= !staged[i].1.exists();
+                if !restored {
+                    logging::error(&format!(
+                        "Keeping {} so the original files can be restored by hand",
+                        staging_dir.display()
+                    ));
+                } else if let Err(e) = fs::remove_dir_all(&staging_dir) {
+                    logging::warn(&format!("Failed to remove {}: {e}", staging_dir.display()));
+                }
+                return Err(e);
+            }
+        }
+
+        for (_, _, target_path) in &staged {
+            logging::info(&format!("Installed {}", target_path.display()));
+        }
+        fs::remove_dir_all(&staging_dir)
+            .with_context(|| format!("Failed to remove {}", staging_dir.display()))?;
+        Ok(())
+    }
+
+    fn copy_with_permissions(&self, src: &path::Path, dst: &path::Path) -> Result<()> {
+        // nested files, e.g. an extension's usr/lib/systemd/system tree
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::copy(src, dst).context(format!(
+            "Failed to copy {} to {}",
+            src.display(),
+            dst.display(),
+        ))?;
+
+        // an explicit mode in [file_permissions] wins over the source mode
+        let mode = match &self.config.file_permissions.mode {
+            Some(mode) => u32::from_str_radix(mode, 8)
+                .with_context(|| format!("Invalid file_permissions.mode '{mode}'"))?,
+            None => fs::metadata(src)?.permissions().mode(),
+        };
+        fs::set_permissions(dst, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set permissions on {}", dst.display()))?;
+
+        Ok(())
+    }
+
+    // the main service followed by any extra_units
+    pub fn unit_names(&self) -> Vec<String> {
+        let mut units = vec![format!("{}.service", self.name)];
+        units.extend(self.config.systemd.extra_units.iter().map(|u| unit_name(u)));
+        units
+    }
+
+    fn hook_command(&self, hook: HookType) -> Option<&str> {
+        let hooks = &self.config.hooks;
+        let command = match hook {
+            HookType::PreInstall => &hooks.pre_install,
+            HookType::PostInstall => &hooks.post_install,
+            HookType::PreUninstall => &hooks.pre_uninstall,
+            HookType::PostUninstall => &hooks.post_uninstall,
+        };
+        command.as_deref()
+    }
+
+    fn run_configured_hook(&self, hook: HookType) -> Result<()> {
+        if self.hook_command(hook).is_some() {
+            self.run_hook(hook)?;
+        }
+        Ok(())
+    }
+
+    fn log_dry_run_hook(&self, hook: HookType) {
+        if let Some(command) = self.hook_command(hook) {
+            logging::info(&format!("[DRY RUN] Would run {hook} hook: {command}"));
+        }
+    }
+
+    fn resource_limits_drop_in(&self) -> Option<String> {
+        let resources = &self.config.resources;
+        let mut content = String::new();
+        if let Some(memory_max) = &resources.memory_max {
+            content.push_str(&format!("MemoryMax={memory_max}\n"));
+        }
+        if let Some(cpu_quota) = &resources.cpu_quota {
+            content.push_str(&format!("CPUQuota={cpu_quota}\n"));
+        }
+        if let Some(tasks_max) = resources.tasks_max {
+            content.push_str(&format!("TasksMax={tasks_max}\n"));
+        }
+
+        if content.is_empty() {
+            return None;
+        }
+        Some(format!("[Service]\n{content}"))
+    }
+
+    // ordering for unit files that shouldn't be edited, e.g. ones shipped upstream
+    fn ordering_drop_in(&self) -> Option<String> {
+        let systemd = &self.config.systemd;
+        let mut content = String::new();
+        if !systemd.after_units.is_empty() {
+            content.push_str(&format!("After={}\n", systemd.after_units.join(" ")));
+        }
+        if !systemd.requires_units.is_empty() {
+            content.push_str(&format!("Requires={}\n", systemd.requires_units.join(" ")));
+        }
+
+        if content.is_empty() {
+            return None;
+        }
+        Some(format!("[Unit]\n{content}"))
+    }
+
+    // stops and restarts the service along with the units it's bound to
+    fn binding_drop_in(&self) -> Option<String> {
+        let systemd = &self.config.systemd;
+        let mut content = String::new();
+        if !systemd.part_of.is_empty() {
+            content.push_str(&format!("PartOf={}\n", systemd.part_of.join(" ")));
+        }
+        if !systemd.binds_to.is_empty() {
+            content.push_str(&format!("BindsTo={}\n", systemd.binds_to.join(" ")));
+        }
+
+        if content.is_empty() {
+            return None;
+        }
+        Some(format!("[Unit]\n{content}"))
+    }
+
+    fn log_namespace_drop_in(&self) -> Option<String> {
+        let namespace = self.log_namespace()?;
+        Some(format!("[Service]\nLogNamespace={namespace}\n"))
+    }
+
+    fn journal_namespace_arg(&self) -> Option<String> {
+        self.log_namespace()
+            .map(|namespace| format!("--namespace={namespace}"))
+    }
+
+    fn logging_drop_in(&self) -> Option<String> {
+        let systemd = &self.config.systemd;
+        let mut content = String::new();
+        if let Some(forwarding) = &systemd.log_forwarding {
+            content.push_str(&format!(
+                "StandardOutput={forwarding}\nStandardError={forwarding}\n"
+            ));
+        }
+        if let Some(level) = &systemd.log_level_max {
+            content.push_str(&format!("LogLevelMax={level}\n"));
+        }
+
+        if content.is_empty() {
+            return None;
+        }
+        Some(format!("[Service]\n{content}"))
+    }
+
+    // e.g. "d /run/myapp 0750 myapp myapp -", fields that aren't set are "-"
+    fn tmpfiles_d_config(&self) -> Option<String> {
+        if self.config.tmpfiles.is_empty() {
+            return None;
+        }
+
+        let mut content = String::new();
+        for entry in &self.config.tmpfiles {
+            let fields = [&entry.mode, &entry.user, &entry.group, &entry.age];
+            let fields: Vec<&str> = fields.iter().map(|f| f.as_deref().unwrap_or("-")).collect();
+            content.push_str(&format!(
+                "{} {} {}\n",
+                entry.entry_type,
+                entry.path,
+                fields.join(" ")
+            ));
+        }
+        Some(content)
+    }
+
+    // e.g. u myapp - "myapp service" /var/lib/myapp
+    fn sysusers_d_config(&self) -> Option<String> {
+        if self.config.sysusers.is_empty() {
+            return None;
+        }
+
+        let mut content = String::new();
+        for entry in &self.config.sysusers {
+            let gecos = match &entry.gecos {
+                Some(gecos) => format!("\"{gecos}\""),
+                None => String::from("-"),
+            };
+            content.push_str(&format!(
+                "{} {} {} {gecos} {}\n",
+                entry.entry_type,
+                entry.name,
+                entry.uid.as_deref().unwrap_or("-"),
+                entry.home.as_deref().unwrap_or("-")
+            ));
+        }
+        Some(content)
+    }
+
+    // unless configured, only runs when SELinux is enforcing
+    fn restore_selinux_context(&self) -> bool {
+        match self.config.systemd.selinux_restore_context {
+            Some(restore) => restore,
+            None => fs::read_to_string(SELINUX_ENFORCE_PATH).is_ok_and(|e| e.trim() == "1"),
+        }
+    }
+
+    fn sysusers_d_path(&self) -> path::PathBuf {
+        path::Path::new(SYSUSERS_DIR).join(format!("{}.conf", self.name))
+    }
+
+    fn tmpfiles_d_path(&self) -> Result<path::PathBuf> {
+        let file_name = format!("{}.conf", self.name);
+        if self.use_user {
+            let home = env::var("HOME").context("HOME is not set")?;
+            Ok(path::Path::new(&home)
+                .join(USER_TMPFILES_DIR)
+                .join(file_name))
+        } else {
+            Ok(path::Path::new(TMPFILES_DIR).join(file_name))
+        }
+    }
+
+    fn systemd_tmpfiles(&self, action: &str, tmpfiles_path: &path::Path) -> Result<()> {
+        let file_name = tmpfiles_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let mut args = vec![action, &file_name];
+        if self.use_user {
+            args.insert(0, "--user");
+        }
+        let output = self.runner.run("systemd-tmpfiles", &args)?;
+        if !output.status.success() {
+            bail!(
+                "systemd-tmpfiles {action} {file_name} failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    // stops restart loops once the service restarted too often within an hour
+    fn start_limit_drop_in(&self) -> Option<String> {
+        let systemd = &self.config.systemd;
+        let mut content = String::new();
+        if let Some(burst) = systemd.max_restarts_per_hour {
+            content.push_str(&format!(
+                "StartLimitIntervalSec=3600\nStartLimitBurst={burst}\n"
+            ));
+        }
+        if let Some(action) = &systemd.on_start_limit_action {
+            content.push_str(&format!("StartLimitAction={action}\n"));
+        }
+
+        if content.is_empty() {
+            return None;
+        }
+        Some(format!("[Unit]\n{content}"))
+    }
+
+    fn watchdog_drop_in(&self) -> Option<String> {
+        if !self.config.systemd.watchdog_enabled {
+            return None;
+        }
+
+        let watchdog_sec = self
+            .config
+            .systemd
+            .watchdog_sec
+            .unwrap_or(DEFAULT_WATCHDOG_SEC);
+
+        // pings come from units rather than the main process
+        Some(format!(
+            "[Service]\nWatchdogSec={watchdog_sec}\nNotifyAccess=all\n"
+        ))
+    }
+
+    fn write_drop_in(&self, file_name: &str, content: &str) -> Result<()> {
+        let drop_in_dir = self.drop_in_dir();
+        fs::create_dir_all(&drop_in_dir)
+            .with_context(|| format!("Failed to create {}", drop_in_dir.display()))?;
+
+        let drop_in_path = drop_in_dir.join(file_name);
+        fs::write(&drop_in_path, content)
+            .with_context(|| format!("Failed to write {}", drop_in_path.display()))?;
+        logging::info(&format!("Wrote {}", drop_in_path.display()));
+
+        Ok(())
+    }
+
+    fn remove_drop_in(&self, file_name: &str, keep_dirs: bool) -> Result<()> {
+        let drop_in_dir = self.drop_in_dir();
+        let drop_in_path = drop_in_dir.join(file_name);
+        if !drop_in_path.exists() {
+            return Ok(());
+        }
+
+        fs::remove_file(&drop_in_path)
+            .with_context(|| format!("Failed to remove {}", drop_in_path.display()))?;
+        logging::info(&format!("Removed file {}", drop_in_path.display()));
+
+        if !keep_dirs && fs::read_dir(&drop_in_dir)?.next().is_none() {
+            fs::remove_dir(&drop_in_dir)
+                .with_context(|| format!("Failed to remove {}", drop_in_dir.display()))?;
+            logging::info(&format!("Removed directory {}", drop_in_dir.display()));
+        }
+
+        Ok(())
+    }
+
+    fn files_installed(&self) -> Result<bool> {
+        for entry in WalkDir::new(&self.app_dir).into_iter() {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_dir() || path.file_name() == Some(ffi::OsStr::new(CONFIG_FILE_NAME)) {
+                continue;
+            }
+
+            let unit_name = path.strip_prefix(&self.app_dir)?;
+            let target_path = self.systemd_dir.join(unit_name);
+            if !target_path.exists() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn get_app_files(&self) -> Result<Vec<path::PathBuf>> {
+        let mut files = Vec::new();
+
+        for entry in WalkDir::new(&self.app_dir).into_iter() {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.is_dir() || path.file_name() == Some(ffi::OsStr::new(CONFIG_FILE_NAME)) {
+                continue;
+            }
+
+            files.push(path.to_path_buf());
+        }
+
+        Ok(files)
+    }
+
+    fn systemctl(&self, args: Vec<String>) -> Result<process::ExitStatus> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.runner.run("systemctl", &args)?;
+
+        // systemctl only prints on failure, pass its messages along
+        io::stderr().write_all(&output.stderr)?;
+        Ok(output.status)
+    }
+
+    fn is_sysext(&self) -> bool {
+        self.config.systemd.extension_mode == Some(ExtensionMode::Sysext)
+    }
+
+    fn sysext(&self, args: &[&str]) -> Result<process::Output> {
+        let output = self.runner.run("systemd-sysext", args)?;
+        if !output.status.success() {
+            bail!(
+                "Failed to run systemd-sysext {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(output)
+    }
+
+    // the extension is named after the app, e.g. /var/lib/extensions/<name>
+    fn extension_merged(&self) -> Result<bool> {
+        let output = self.sysext(&["status", "--json=short"])?;
+        let hierarchies: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
+            .context("Failed to parse systemd-sysext status")?;
+
+        // "extensions" is the string "none" when nothing is merged
+        Ok(hierarchies.iter().any(|hierarchy| {
+            hierarchy["extensions"]
+                .as_array()
+                .is_some_and(|exts| exts.iter().any(|e| e.as_str() == Some(&self.name)))
+        }))
+    }
+
+    fn prepare_systemctl_args(&self, mut args: Vec<String>) -> Vec<String> {
+        if self.use_user {
+            args.insert(0, "--user".to_string());
+        }
+        args
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LogsOptions {
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LogRecord {
+    pub timestamp: Option<String>,
+    pub priority: Option<String>,
+    pub message: String,
+    pub invocation_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AppStatus {
+    NotInstalled,
+    Installed,
+    Stopped,
+    Running,
+    Failed,
+    StartLimitHit,
+    Masked,
+}
+
+impl fmt::Display for AppStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppStatus::NotInstalled => write!(f, "Not Installed"),
+            AppStatus::Installed => write!(f, "Installed"),
+            AppStatus::Stopped => write!(f, "Stopped"),
+            AppStatus::Running => write!(f, "Running"),
+            AppStatus::Failed => write!(f, "Failed"),
+            AppStatus::StartLimitHit => write!(f, "Start Limit Hit"),
+            AppStatus::Masked => write!(f, "Masked"),
+        }
+    }
+}
+
+pub struct Warning {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    // line 0 means the warning is about the file as a whole
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+pub struct VerifyReport {
+    pub unit: path::PathBuf,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+pub struct SecurityReport {
+    pub score: f64,
+    pub exposure: String,
+    pub warnings: Vec<String>,
+}
 
 #[derive(Deserialize)]
-struct Systemd {
-    install_location: String,
+struct SecurityCheck {
+    set: Option<bool>,
+    description: String,
+}
+
+// a plain HTTP/1.0 GET, anything below 400 counts as healthy like curl -f.
+// unresolvable hosts, connection errors and timeouts are a failed attempt
+fn http_get_succeeds(url: &str, timeout: time::Duration) -> Result<bool> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        bail!("Health check url '{url}' must be an http:// url");
+    };
+    let (authority, request_path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    let address = if has_port {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+    let Ok(addrs) = net::ToSocketAddrs::to_socket_addrs(&address) else {
+        return Ok(false);
+    };
+
+    for addr in addrs {
+        let Ok(mut stream) = net::TcpStream::connect_timeout(&addr, timeout) else {
+            continue;
+        };
+        let status_line = stream
+            .set_read_timeout(Some(timeout))
+            .and_then(|_| stream.set_write_timeout(Some(timeout)))
+            .and_then(|_| {
+                write!(
+                    stream,
+                    "GET {request_path} HTTP/1.0\r\nHost: {authority}\r\nConnection: close\r\n\r\n"
+                )
+            })
+            .and_then(|_| {
+                let mut line = String::new();
+                io::BufReader::new(&stream).read_line(&mut line)?;
+                Ok(line)
+            });
+        // e.g. "HTTP/1.1 200 OK"
+        let code = status_line.ok().and_then(|line| {
+            line.split_whitespace()
+                .nth(1)
+                .and_then(|code| code.parse::<u16>().ok())
+        });
+        return Ok(code.is_some_and(|code| code < 400));
+    }
+    Ok(false)
+}
+
+fn env_drop_in(env: &[String]) -> Result<Option<String>> {
+    if env.is_empty() {
+        return Ok(None);
+    }
+
+    let mut content = String::from("[Service]\n");
+    for variable in env {
+        if !variable.contains('=') {
+            bail!("Invalid environment variable '{variable}', expected KEY=VALUE");
+        }
+        // quoted so spaces survive, a quote in the value must not end it
+        let quoted = variable
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        content.push_str(&format!("Environment=\"{quoted}\"\n"));
+    }
+
+    Ok(Some(content))
+}
+
+fn deprecated_directive(key: &str, value: &str) -> Option<String> {
+    let message = match key {
+        "StandardOutput" | "StandardError" if value.starts_with("syslog") => {
+            format!("{key}={value} is deprecated, use journal instead")
+        }
+        "PermissionsStartOnly" => {
+            String::from("PermissionsStartOnly= is deprecated, prefix commands with + instead")
+        }
+        "MemoryLimit" => String::from("MemoryLimit= is deprecated, use MemoryMax= instead"),
+        "CPUShares" => String::from("CPUShares= is deprecated, use CPUWeight= instead"),
+        "BlockIOWeight" => String::from("BlockIOWeight= is deprecated, use IOWeight= instead"),
+        _ => return None,
+    };
+    Some(message)
+}
+
+// "myapp-worker" -> "myapp-worker.service", other unit types are kept as is
+fn unit_name(unit: &str) -> String {
+    if unit.contains('.') {
+        unit.to_string()
+    } else {
+        format!("{unit}.service")
+    }
+}
+
+// replaces `name` where it isn't part of a longer name or a path, so cloning
+// "web" leaves "webhook" and "/usr/bin/web" alone
+fn replace_name(content: &str, name: &str, new_name: &str) -> String {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    let is_path_char = |c: char| is_name_char(c) || c == '/' || c == '.';
+    let mut replaced = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(name) {
+        let before = rest[..start].chars().next_back();
+        let after = rest[start + name.len()..].chars().next();
+        replaced.push_str(&rest[..start]);
+        if before.is_some_and(is_path_char) || after.is_some_and(is_name_char) {
+            replaced.push_str(name);
+        } else {
+            replaced.push_str(new_name);
+        }
+        rest = &rest[start + name.len()..];
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+// moves a replaced file aside so it can be restored if a later file fails
+fn move_into_place(
+    staged_path: &path::Path,
+    backup_path: &path::Path,
+    target_path: &path::Path,
+    overwrite: bool,
+) -> Result<()> {
+    if target_path.exists() {
+        if !overwrite {
+            bail!(
+                "File {} already exists. Use --overwrite to overwrite.",
+                target_path.display()
+            );
+        }
+        if let Some(parent) = backup_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(target_path, backup_path)
+            .with_context(|| format!("Failed to move {} aside", target_path.display()))?;
+    }
+    let result = target_path
+        .parent()
+        .map_or(Ok(()), |parent| {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))
+        })
+        .and_then(|()| {
+            fs::rename(staged_path, target_path).with_context(|| {
+                format!(
+                    "Failed to move {} to {}",
+                    staged_path.display(),
+                    target_path.display()
+                )
+            })
+        });
+    // put the original back before the caller rolls back the earlier files
+    if result.is_err()
+        && backup_path.exists()
+        && let Err(e) = fs::rename(backup_path, target_path)
+    {
+        logging::error(&format!("Failed to restore {}: {e}", target_path.display()));
+    }
+    result
+}
+
+// returns false if the original file is still in the staging directory
+fn roll_back(staged_path: &path::Path, backup_path: &path::Path, target_path: &path::Path) -> bool {
+    if let Err(e) = fs::rename(target_path, staged_path) {
+        logging::error(&format!(
+            "Failed to roll back {}: {e}",
+            target_path.display()
+        ));
+        return !backup_path.exists();
+    }
+    if backup_path.exists()
+        && let Err(e) = fs::rename(backup_path, target_path)
+    {
+        logging::error(&format!("Failed to restore {}: {e}", target_path.display()));
+        return false;
+    }
+    true
+}
+
+// parses "→ Overall exposure level for myapp.service: 9.6 UNSAFE 😨"
+fn parse_exposure_level(line: &str) -> Option<(f64, String)> {
+    let (_, level) = line.split_once("Overall exposure level for")?;
+    let (_, level) = level.split_once(": ")?;
+    let mut parts = level.split_whitespace();
+    let score = parts.next()?.parse().ok()?;
+    let exposure = parts.next().unwrap_or_default().to_string();
+    Some((score, exposure))
+}
+
+fn read_cpu_usage_usec(stat_file: &path::Path) -> Option<u64> {
+    let content = fs::read_to_string(stat_file).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|usage| usage.trim().parse().ok())
+}
+
+// parses "Archived and active journals take up 56.0M in the file system."
+fn parse_disk_usage(output: &str) -> Option<u64> {
+    let (_, size) = output.split_once("take up ")?;
+    let size = size.split_whitespace().next()?;
+    let split = size.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (number, suffix) = size.split_at(split);
+    let multiplier: u64 = match suffix {
+        "B" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * multiplier as f64) as u64)
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct AppConfig {
+    pub systemd: Systemd,
+    #[serde(default)]
+    pub service: Service,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub file_permissions: FilePermissions,
+    #[serde(default)]
+    pub resources: Resources,
+    #[serde(default)]
+    pub alerts: Alerts,
+    pub health_check: Option<HealthCheck>,
+    #[serde(default)]
+    pub tmpfiles: Vec<TmpFile>,
+    #[serde(default)]
+    pub sysusers: Vec<SysUser>,
+}
+
+impl AppConfig {
+    // collects every problem so they can all be fixed in one go
+    pub fn validate(&self, app_name: &str, app_dir: &path::Path) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        let install_location = path::Path::new(&self.systemd.install_location);
+        if !install_location.is_absolute() {
+            errors.push(format!(
+                "systemd.install_location '{}' must be an absolute path",
+                self.systemd.install_location
+            ));
+        }
+        if install_location
+            .components()
+            .any(|c| c == path::Component::ParentDir)
+        {
+            errors.push(format!(
+                "systemd.install_location '{}' must not contain '..'",
+                self.systemd.install_location
+            ));
+        }
+        if let Some(check) = &self.health_check
+            && !check.url.starts_with("http://")
+        {
+            errors.push(format!(
+                "health_check.url '{}' must be an http:// url",
+                check.url
+            ));
+        }
+        if let Some(timeout) = self.systemd.stop_timeout_secs
+            && !(1..300).contains(&timeout)
+        {
+            errors.push(format!(
+                "systemd.stop_timeout_secs must be between 1 and 299, got {timeout}"
+            ));
+        }
+
+        let hooks = [
+            ("pre_install", &self.hooks.pre_install),
+            ("post_install", &self.hooks.post_install),
+            ("pre_uninstall", &self.hooks.pre_uninstall),
+            ("post_uninstall", &self.hooks.post_uninstall),
+        ];
+        for (name, hook) in hooks {
+            // only hooks that start with a path can be checked, others are looked up in PATH
+            let Some(program) = hook.as_deref().and_then(|h| h.split_whitespace().next()) else {
+                continue;
+            };
+            if program.contains('/') && !app_dir.join(program).exists() {
+                errors.push(format!("hooks.{name} '{program}' does not exist"));
+            }
+        }
+
+        if let Some(check) = &self.health_check {
+            if !check.url.starts_with("http://") && !check.url.starts_with("https://") {
+                errors.push(format!(
+                    "health_check.url '{}' must be an http:// or https:// URL",
+                    check.url
+                ));
+            }
+            if check.retries == 0 {
+                errors.push(String::from("health_check.retries must be at least 1"));
+            }
+        }
+        if let Some(namespace) = &self.systemd.log_namespace_id {
+            let valid = !namespace.is_empty()
+                && namespace
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+            if !valid {
+                errors.push(format!(
+                    "systemd.log_namespace_id '{namespace}' may only contain letters, digits, '-', '_' and '.'"
+                ));
+            }
+            // journald namespaces only exist for the system instance
+            if self.systemd.use_user {
+                errors.push(String::from(
+                    "systemd.log_namespace_id can't be used with use_user",
+                ));
+            }
+        }
+        if self.systemd.max_restarts_per_hour == Some(0) {
+            errors.push(String::from(
+                "systemd.max_restarts_per_hour must be at least 1",
+            ));
+        }
+        if let Some(level) = &self.systemd.log_level_max
+            && !LOG_PRIORITIES.contains(&level.as_str())
+        {
+            errors.push(format!(
+                "systemd.log_level_max must be one of {}, got '{level}'",
+                LOG_PRIORITIES.join(", ")
+            ));
+        }
+
+        for entry in &self.tmpfiles {
+            if !path::Path::new(&entry.path).is_absolute() {
+                errors.push(format!(
+                    "tmpfiles path '{}' must be an absolute path",
+                    entry.path
+                ));
+            }
+            if entry.entry_type.is_empty() || entry.entry_type.contains(char::is_whitespace) {
+                errors.push(format!(
+                    "tmpfiles type for '{}' must be a single tmpfiles.d type, e.g. \"d\"",
+                    entry.path
+                ));
+            }
+        }
+
+        if !self.sysusers.is_empty() && self.systemd.use_user {
+            errors.push(String::from(
+                "sysusers can only be used by system apps, set systemd.use_user to false",
+            ));
+        }
+        for entry in &self.sysusers {
+            if !["u", "g", "m", "r"].contains(&entry.entry_type.as_str()) {
+                errors.push(format!(
+                    "sysusers type for '{}' must be one of u, g, m, r, got '{}'",
+                    entry.name, entry.entry_type
+                ));
+            }
+            if entry.gecos.as_deref().is_some_and(|g| g.contains('"')) {
+                errors.push(format!(
+                    "sysusers gecos for '{}' must not contain quotes",
+                    entry.name
+                ));
+            }
+        }
+
+        if self.systemd.depends_on.iter().any(|d| d == app_name) {
+            errors.push(format!(
+                "systemd.depends_on must not list {app_name} itself"
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct Systemd {
+    pub install_location: String,
+    pub use_user: bool,
+    #[serde(default = "default_true")]
+    pub create_install_dir: bool,
+    pub stop_timeout_secs: Option<u32>,
+    #[serde(default)]
+    pub watchdog_enabled: bool,
+    pub watchdog_sec: Option<u32>,
+    #[serde(default)]
+    pub fd_store: bool,
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    #[serde(default)]
+    pub extra_units: Vec<String>,
+    #[serde(default)]
+    pub after_units: Vec<String>,
+    #[serde(default)]
+    pub requires_units: Vec<String>,
+    #[serde(default)]
+    pub part_of: Vec<String>,
+    #[serde(default)]
+    pub binds_to: Vec<String>,
+    pub extension_mode: Option<ExtensionMode>,
+    #[serde(default)]
+    pub restart_mode: RestartMode,
+    #[serde(default)]
+    pub atomic_install: bool,
+    pub log_forwarding: Option<LogForwarding>,
+    pub log_level_max: Option<String>,
+    pub log_namespace_id: Option<String>,
+    pub selinux_restore_context: Option<bool>,
+    pub max_restarts_per_hour: Option<u32>,
+    pub on_start_limit_action: Option<StartLimitAction>,
+    #[serde(default)]
+    pub create_service_user: bool,
+    pub service_user: Option<String>,
+    #[serde(default)]
+    pub journalctl_bin: JournalctlPath,
+    #[serde(default)]
+    pub analyze_bin: AnalyzePath,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct JournalctlPath(pub String);
+
+impl Default for JournalctlPath {
+    fn default() -> Self {
+        JournalctlPath(String::from("journalctl"))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub struct AnalyzePath(pub String);
+
+impl Default for AnalyzePath {
+    fn default() -> Self {
+        AnalyzePath(String::from("systemd-analyze"))
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtensionMode {
+    Sysext,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogForwarding {
+    Syslog,
+    Journal,
+    Kmsg,
+}
+
+impl fmt::Display for LogForwarding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogForwarding::Syslog => write!(f, "syslog"),
+            LogForwarding::Journal => write!(f, "journal"),
+            LogForwarding::Kmsg => write!(f, "kmsg"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum StartLimitAction {
+    RebootForce,
+    Poweroff,
+    None,
+}
+
+impl fmt::Display for StartLimitAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartLimitAction::RebootForce => write!(f, "reboot-force"),
+            StartLimitAction::Poweroff => write!(f, "poweroff"),
+            StartLimitAction::None => write!(f, "none"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartMode {
+    #[default]
+    Restart,
+    StopStart,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Service {
+    pub exec_start: Option<String>,
+    pub user: Option<String>,
+    pub working_directory: Option<String>,
+    pub restart: Option<String>,
+    pub wanted_by: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Resources {
+    pub memory_max: Option<String>,
+    pub cpu_quota: Option<String>,
+    pub tasks_max: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct FilePermissions {
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Hooks {
+    pub pre_install: Option<String>,
+    pub post_install: Option<String>,
+    pub pre_uninstall: Option<String>,
+    pub post_uninstall: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Alerts {
+    pub notify_command: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct HealthCheck {
+    pub url: String,
+    #[serde(default = "default_health_check_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_health_check_retries")]
+    pub retries: u32,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TmpFile {
+    pub path: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub mode: Option<String>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub age: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SysUser {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub uid: Option<String>,
+    pub gecos: Option<String>,
+    pub home: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_health_check_timeout() -> u64 {
+    10
+}
+
+fn default_health_check_retries() -> u32 {
+    3
+}
+
+fn read_config(config_path: &path::Path) -> Result<AppConfig> {
+    let config_str = fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to find config file at {}", config_path.display()))?;
+    let config = toml::from_str(&config_str)?;
+    Ok(config)
+}
+
+// the config written for new apps, just enough for them to load
+#[derive(Serialize)]
+struct NewAppConfig<'a> {
+    systemd: NewAppSystemd<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<NewAppService<'a>>,
+}
+
+#[derive(Serialize)]
+struct NewAppSystemd<'a> {
+    install_location: &'a str,
+    use_user: bool,
+}
+
+#[derive(Serialize)]
+struct NewAppService<'a> {
+    exec_start: &'a str,
+    restart: &'a str,
+}
+
+// serialized rather than formatted so quotes and backslashes in paths and
+// commands are escaped, and validated before anything is written
+pub fn new_app_config(
+    app_name: &str,
+    app_dir: &path::Path,
+    install_location: &path::Path,
     use_user: bool,
+    exec_start: Option<&str>,
+) -> Result<String> {
+    let install_location = install_location
+        .to_str()
+        .ok_or_else(|| anyhow!("{} is not valid UTF-8", install_location.display()))?;
+    let config = toml::to_string(&NewAppConfig {
+        systemd: NewAppSystemd {
+            install_location,
+            use_user,
+        },
+        service: exec_start.map(|exec_start| NewAppService {
+            exec_start,
+            restart: "on-failure",
+        }),
+    })?;
+
+    let parsed: AppConfig = toml::from_str(&config)?;
+    parsed
+        .validate(app_name, app_dir)
+        .map_err(|errors| anyhow!("Invalid config for {app_name}:\n  {}", errors.join("\n  ")))?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_get_succeeds() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/healthz", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            for status in ["200 OK", "503 Service Unavailable"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request_line = String::new();
+                io::BufReader::new(&stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                assert_eq!(request_line, "GET /healthz HTTP/1.0\r\n");
+                write!(stream, "HTTP/1.1 {status}\r\n\r\n").unwrap();
+            }
+        });
+
+        let timeout = time::Duration::from_secs(5);
+        assert!(http_get_succeeds(&url, timeout).unwrap());
+        assert!(!http_get_succeeds(&url, timeout).unwrap());
+        server.join().unwrap();
+        assert!(http_get_succeeds("https://localhost/", timeout).is_err());
+    }
 }
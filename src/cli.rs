@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::{fmt, net, path};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -6,23 +7,394 @@ pub struct Args {
     #[command(subcommand)]
     pub command: Commands,
 
-    /// Skip confirmations
+    /// Deprecated: same as --no-confirm --overwrite
     #[arg(long)]
     pub force: bool,
 
+    /// Skip confirmations
+    #[arg(long)]
+    pub no_confirm: bool,
+
+    /// Overwrite existing files
+    #[arg(long)]
+    pub overwrite: bool,
+
     /// Show plan without executing
     #[arg(long)]
     pub dry_run: bool,
+
+    /// Output format [default: text]
+    #[arg(long, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Show more detail
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Seconds to wait for a systemctl command before giving up
+    #[arg(long, default_value_t = 30)]
+    pub systemctl_timeout: u64,
+
+    /// Disable colored output
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Always query systemd instead of reusing recently fetched statuses
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// How many directory levels to scan for apps; nested apps are only warned about [default: 1]
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub app_depth: Option<usize>,
+
+    /// Stable tab separated output for scripts (status, install and uninstall)
+    #[arg(long)]
+    pub porcelain: bool,
+
+    /// Read the app's config from this file instead of <app>/config.toml (single-app commands only)
+    #[arg(long)]
+    pub app_config: Option<path::PathBuf>,
+
+    /// Manage the apps listed in this manifest instead of discovering them [default: units.toml if present]
+    #[arg(long)]
+    pub manifest: Option<path::PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Ndjson,
+    Yaml,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum ExitCodeMode {
+    AnyRunning,
+    AllRunning,
+    NoneFailed,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum HookType {
+    PreInstall,
+    PostInstall,
+    PreUninstall,
+    PostUninstall,
+}
+
+impl fmt::Display for HookType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookType::PreInstall => write!(f, "pre-install"),
+            HookType::PostInstall => write!(f, "post-install"),
+            HookType::PreUninstall => write!(f, "pre-uninstall"),
+            HookType::PostUninstall => write!(f, "post-uninstall"),
+        }
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// Show status of apps
-    Status { app_name: Option<String> },
+    Status {
+        app_name: Option<String>,
+
+        /// Keep refreshing the status until interrupted
+        #[arg(long)]
+        watch: bool,
+
+        /// Seconds between refreshes when watching
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+
+        /// Don't run the apps' notify commands when they fail while watching
+        #[arg(long)]
+        no_alerts: bool,
+
+        /// Only show apps whose status changed since the last status run
+        #[arg(long, conflicts_with = "watch")]
+        diff_from_last_run: bool,
+
+        /// Exit with code 1 unless the apps' statuses meet this condition
+        #[arg(long, value_enum, conflicts_with = "watch")]
+        exit_code: Option<ExitCodeMode>,
+    },
+    /// Show details about an app
+    Inspect { app_name: String },
+    /// Show a summary of app statuses, exiting non-zero if any app failed
+    Summary,
+    /// List all apps with their status, memory use and security exposure
+    List {
+        /// Sort from the most to the least exposed app
+        #[arg(long)]
+        sort_by_security: bool,
+    },
+    /// Serve Prometheus metrics for all apps
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 9100)]
+        port: u16,
+
+        /// Address to listen on, e.g. 0.0.0.0 to accept remote scrapes
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: net::IpAddr,
+    },
+    /// Print the app dependency graph in DOT format (render with `dot -Tsvg > graph.svg`)
+    Graph {
+        /// Write the graph to this file instead of stdout
+        #[arg(long)]
+        output: Option<path::PathBuf>,
+    },
+    /// Create a new app with a config and generated unit file
+    Init {
+        app_name: String,
+
+        /// Command the service runs
+        #[arg(long)]
+        exec_start: Option<String>,
+    },
+    /// Turn an existing directory of unit files into an app
+    Register {
+        path: path::PathBuf,
+
+        /// Name of the app, defaults to the directory's name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Turn an already installed unit file into an app without reinstalling it
+    #[command(name = "import-systemd-unit")]
+    ImportUnit {
+        unit_path: path::PathBuf,
+
+        /// Name of the app, defaults to the unit's name
+        #[arg(long)]
+        app_name: Option<String>,
+    },
+    /// Copy an app under a new name, e.g. for a staging variant
+    Clone {
+        app_name: String,
+        new_name: String,
+
+        /// Repo to copy the app into, defaults to this one
+        #[arg(long)]
+        dest_repo: Option<path::PathBuf>,
+    },
+    /// Print the unit file generated from an app's config
+    Generate { app_name: String },
     /// Install an app
-    Install { app_name: Option<String> },
+    Install {
+        app_name: Option<String>,
+
+        /// Extra environment variables to set for the service
+        #[arg(long = "env", value_name = "KEY=VALUE", num_args = 0..)]
+        env: Vec<String>,
+
+        /// Keep installing the remaining apps after one fails
+        #[arg(long)]
+        continue_on_error: bool,
+
+        /// Refuse to install when unit files have lint warnings
+        #[arg(long)]
+        strict: bool,
+
+        /// Install to this directory instead of the configured install location
+        #[arg(long)]
+        install_dir: Option<path::PathBuf>,
+
+        /// Check the system requirements before installing
+        #[arg(long)]
+        preflight: bool,
+
+        /// Install even if another app installs the same unit file
+        #[arg(long)]
+        allow_conflicts: bool,
+
+        /// Poll the app's [health_check] url after starting it
+        #[arg(long)]
+        health_check: bool,
+    },
     /// Uninstall an app
-    Uninstall { app_name: Option<String> },
+    Uninstall {
+        app_name: Option<String>,
+
+        /// Keep empty directories left behind in the install location
+        #[arg(long)]
+        keep_dirs: bool,
+
+        /// Also delete the app directory from the repo
+        #[arg(long)]
+        prune: bool,
+
+        /// Warn instead of failing when an installed file can't be removed
+        #[arg(long)]
+        ignore_remove_errors: bool,
+
+        /// Also remove drop-in files not created by the install, such as overrides
+        #[arg(long)]
+        remove_overrides: bool,
+    },
+    /// Stop, remove and install apps again, e.g. after an OS upgrade
+    Reinstall { app_name: Option<String> },
+    /// Install the apps left over from an interrupted or failed bulk install
+    Resume,
+    /// Undo the most recent install or uninstall
+    Undo {
+        /// List the recorded operations instead of undoing one
+        #[arg(long)]
+        list: bool,
+    },
+    /// Show how the installed files differ from the app's files
+    Diff {
+        app_name: String,
+
+        /// Number of context lines around each change
+        #[arg(long, default_value_t = 3)]
+        context: usize,
+    },
+    /// Restart an app's units
+    Restart { app_name: String },
+    /// Enable every installed app to start on boot
+    EnableAll,
+    /// Disable every installed app from starting on boot
+    DisableAll,
+    /// Reset the failed state of an app's units, e.g. after hitting the start limit
+    ResetFailed { app_name: String },
+    /// Unmask an app's service so it can be started again
+    Unmask { app_name: String },
+    /// Show the environment variables set for an app's service
+    Env { app_name: String },
     /// Show logs for an app
-    Logs { app_name: String },
+    Logs {
+        app_name: String,
+
+        /// Show logs for one of the app's extra_units instead of the main service
+        #[arg(long)]
+        unit: Option<String>,
+
+        /// Only show entries since the app was last installed
+        #[arg(long)]
+        since_install: bool,
+
+        /// Only show entries from one run of the service, by its InvocationID
+        #[arg(long)]
+        invocation: Option<String>,
+    },
+    /// Save an app's logs in journald's export format, gzipped for a .gz path
+    ExportLogs {
+        app_name: String,
+
+        /// File to write, e.g. myapp.export or myapp.export.gz
+        #[arg(long)]
+        output: path::PathBuf,
+
+        /// Only export entries since this time, e.g. "1 hour ago" or "today"
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only export entries until this time
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Rotate an app's journal namespace and drop its archived entries
+    RotateLogs { app_name: String },
+    /// Drop archived entries of an app's journal namespace beyond a size or age
+    VacuumLogs {
+        app_name: String,
+
+        /// Keep at most this much archived journal, e.g. "500M"
+        #[arg(long)]
+        max_size: Option<String>,
+
+        /// Drop archived entries older than this, e.g. "2weeks"
+        #[arg(long)]
+        max_time: Option<String>,
+    },
+    /// Show the journal namespaces and which apps log to them
+    ListNamespaces,
+    /// Count an app's log messages by priority
+    LogStats {
+        app_name: Option<String>,
+
+        /// Only count messages since this time, e.g. "1 hour ago" or "today"
+        #[arg(long)]
+        since: Option<String>,
+    },
+    /// Check the apps' unit files with systemd-analyze verify
+    Test { app_name: Option<String> },
+    /// Show the CPU and memory usage of running apps
+    Resources {
+        app_name: Option<String>,
+
+        /// Sort by CPU usage and refresh every second until interrupted
+        #[arg(long)]
+        top: bool,
+    },
+    /// Show how much disk space each app's journal and unit files use
+    DiskUsage { app_name: Option<String> },
+    /// Remove old uninstall backups and state left by removed apps
+    Gc {
+        /// Only remove files last modified more than this many days ago
+        #[arg(long, default_value_t = 30)]
+        older_than_days: u64,
+    },
+    /// Check that the system meets the requirements of units
+    Doctor,
+    /// Show the version of units
+    Version {
+        /// Compare it with the latest release, exiting with code 1 if outdated
+        #[arg(long)]
+        check: bool,
+    },
+    /// Reload systemd and check that no app is left stale
+    ReloadDaemon,
+    /// Send watchdog pings for an app until interrupted
+    Watchdog { app_name: String },
+    /// Tell systemd that an app's notify service is ready
+    NotifyReady { app_name: String },
+    /// Run one of an app's configured hooks
+    RunHook {
+        app_name: String,
+        #[arg(value_enum)]
+        hook: HookType,
+    },
+    /// Run a one-off command in the environment of an app's service
+    Run {
+        app_name: String,
+
+        /// Command to run, passed after --
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Show the systemd security exposure of apps
+    Security { app_name: Option<String> },
+    /// Add a drop-in override to an app
+    Override {
+        app_name: String,
+        property: String,
+        value: String,
+
+        /// Unit file section the property belongs to
+        #[arg(long, default_value = "Service")]
+        section: String,
+
+        /// Restart the service after applying the override
+        #[arg(long)]
+        restart: bool,
+    },
+    /// Edit the app's override in $EDITOR and reload systemd
+    Edit {
+        app_name: String,
+
+        /// Restart the service after editing
+        #[arg(long)]
+        restart: bool,
+    },
+    /// List drop-in files for an app
+    ListOverrides { app_name: String },
+    /// Print every drop-in file for an app along with its content
+    ListDropIns { app_name: String },
+    /// Remove the drop-in override created by units
+    RemoveOverride { app_name: String },
 }
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result, bail};
+use std::io::{self, Read};
+use std::{env, path, process, thread, time};
+
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(50);
+
+pub trait CommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<process::Output>;
+}
+
+pub struct RealCommandRunner {
+    timeout: time::Duration,
+}
+
+impl RealCommandRunner {
+    pub fn new(timeout: time::Duration) -> Self {
+        RealCommandRunner { timeout }
+    }
+}
+
+impl CommandRunner for RealCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<process::Output> {
+        output_with_timeout(process::Command::new(program).args(args), self.timeout)
+    }
+}
+
+// replays pre-configured results in order, failing on any unexpected call
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockCommandRunner {
+    expected: std::cell::RefCell<std::collections::VecDeque<(String, process::Output)>>,
+}
+
+#[cfg(test)]
+impl MockCommandRunner {
+    pub fn expect(&self, command: &str, code: i32, stdout: &str) {
+        use std::os::unix::process::ExitStatusExt;
+
+        let output = process::Output {
+            status: process::ExitStatus::from_raw(code << 8),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        };
+        self.expected
+            .borrow_mut()
+            .push_back((command.to_string(), output));
+    }
+
+    // whether every expected command was run
+    pub fn is_done(&self) -> bool {
+        self.expected.borrow().is_empty()
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for MockCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<process::Output> {
+        let command = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        match self.expected.borrow_mut().pop_front() {
+            Some((expected, output)) if expected == command => Ok(output),
+            Some((expected, _)) => bail!("Expected '{expected}' but ran '{command}'"),
+            None => bail!("Unexpected command '{command}'"),
+        }
+    }
+}
+
+// $EDITOR may include arguments, e.g. "code --wait"
+pub fn open_in_editor(path: &path::Path) -> Result<()> {
+    let editor = env::var("EDITOR")
+        .or_else(|_| env::var("VISUAL"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let mut parts = editor.split_whitespace();
+    let Some(program) = parts.next() else {
+        bail!("No editor found, set $EDITOR")
+    };
+
+    let status = match process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            bail!("Editor '{program}' not found, set $EDITOR")
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to run {program}")),
+    };
+    if !status.success() {
+        bail!("{program} exited with {status}");
+    }
+
+    Ok(())
+}
+
+fn output_with_timeout(
+    cmd: &mut process::Command,
+    timeout: time::Duration,
+) -> Result<process::Output> {
+    let operation = describe(cmd);
+    let mut child = cmd
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {operation}"))?;
+
+    // drain the pipes while waiting so a chatty child can't block on a full pipe
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    // polled rather than waited on from a thread, which would take the child
+    // and leave nothing to kill it through on a timeout
+    let started = time::Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if started.elapsed() >= timeout {
+            child
+                .kill()
+                .with_context(|| format!("Failed to kill {operation}"))?;
+            child.wait()?;
+            bail!("'{operation}' timed out after {}s", timeout.as_secs_f64());
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    Ok(process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+fn describe(cmd: &process::Command) -> String {
+    let mut parts = vec![cmd.get_program().to_string_lossy().to_string()];
+    parts.extend(cmd.get_args().map(|a| a.to_string_lossy().to_string()));
+    parts.join(" ")
+}
@@ -0,0 +1,59 @@
+use std::fmt::Write;
+
+use crate::app::{App, AppStatus};
+
+pub fn build_dot_graph(apps: &[(App, AppStatus)]) -> String {
+    let mut out = String::new();
+
+    out.push_str("// render with: dot -Tsvg > graph.svg\n");
+    out.push_str("digraph units {\n");
+    out.push_str("    node [style=filled];\n");
+    for (app, status) in apps {
+        let _ = writeln!(
+            out,
+            "    \"{}\" [fillcolor={}];",
+            escape_id(&app.name),
+            status_color(status)
+        );
+    }
+    for (app, _) in apps {
+        for dependency in &app.config().systemd.depends_on {
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\";",
+                escape_id(&app.name),
+                escape_id(dependency)
+            );
+        }
+    }
+    out.push_str("}\n");
+
+    out
+}
+
+// names are quoted ids, a quote or trailing backslash would end them early
+fn escape_id(name: &str) -> String {
+    name.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn status_color(status: &AppStatus) -> &'static str {
+    match status {
+        AppStatus::Running => "green",
+        AppStatus::Failed | AppStatus::StartLimitHit => "red",
+        AppStatus::NotInstalled | AppStatus::Masked => "grey",
+        AppStatus::Installed | AppStatus::Stopped => "white",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_id() {
+        assert_eq!(escape_id("web"), "web");
+        assert_eq!(escape_id("a\"b\\"), "a\\\"b\\\\");
+    }
+}
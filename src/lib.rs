@@ -1,5 +1,14 @@
 pub mod app;
 pub mod cli;
+pub mod exec;
+pub mod graph;
 pub mod logging;
 pub mod manager;
+pub mod manifest;
+pub mod metrics;
+pub mod progress;
+pub mod status_cache;
 pub mod systemd;
+pub mod timestamp;
+pub mod undo;
+pub mod unit_file;
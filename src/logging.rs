@@ -1,31 +1,82 @@
 use colored::Colorize;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 const LOG_LEVEL_WIDTH: usize = 7;
 
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static PREFIXES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+// prefixes every message with `[prefix]` until the returned scope is dropped
+pub fn with_prefix(prefix: &str) -> LogScope {
+    PREFIXES.with_borrow_mut(|prefixes| prefixes.push(prefix.to_string()));
+    LogScope { _private: () }
+}
+
+pub struct LogScope {
+    _private: (),
+}
+
+impl Drop for LogScope {
+    fn drop(&mut self) {
+        PREFIXES.with_borrow_mut(|prefixes| prefixes.pop());
+    }
+}
+
+fn prefix() -> String {
+    PREFIXES.with_borrow(|prefixes| {
+        if prefixes.is_empty() {
+            return String::new();
+        }
+        let scopes: Vec<String> = prefixes.iter().map(|p| format!("[{p}]")).collect();
+        format!("  {} ", scopes.join(" "))
+    })
+}
+
+// silences all messages, for output meant to be parsed
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
 pub fn info(msg: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
     let level = format!("{:<width$}", "info", width = LOG_LEVEL_WIDTH)
         .blue()
         .bold();
-    println!("{level} {msg}");
+    println!("{}{level} {msg}", prefix());
 }
 
 pub fn success(msg: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
     let level = format!("{:<width$}", "success", width = LOG_LEVEL_WIDTH)
         .green()
         .bold();
-    println!("{level} {msg}");
+    println!("{}{level} {msg}", prefix());
 }
 
 pub fn warn(msg: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
     let level = format!("{:<width$}", "warning", width = LOG_LEVEL_WIDTH)
         .yellow()
         .bold();
-    println!("{level} {msg}");
+    println!("{}{level} {msg}", prefix());
 }
 
 pub fn error(msg: &str) {
+    if QUIET.load(Ordering::Relaxed) {
+        return;
+    }
     let level = format!("{:<width$}", "error", width = LOG_LEVEL_WIDTH)
         .red()
         .bold();
-    println!("{level} {msg}");
+    println!("{}{level} {msg}", prefix());
 }
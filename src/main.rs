@@ -1,16 +1,165 @@
-use anyhow::Result;
-use clap::Parser;
-use units::cli::{Args, Commands};
-use units::manager::Manager;
+use anyhow::{Result, anyhow, bail};
+use clap::{Parser, ValueEnum};
+use std::{env, time};
+use units::cli::{Args, Commands, OutputFormat};
+use units::logging;
+use units::manager::{InstallOptions, Manager};
 
 fn main() -> Result<()> {
-    let args = Args::parse();
-    let manager = Manager::new(args.force, args.dry_run)?;
+    let mut args = Args::parse();
+    if args.force {
+        logging::warn("--force is deprecated, use --no-confirm and/or --overwrite instead");
+    }
+
+    // environment variables only fill in what wasn't passed on the command line
+    args.force |= env_flag("UNITS_FORCE");
+    args.dry_run |= env_flag("UNITS_DRY_RUN");
+    if args.output.is_none()
+        && let Ok(value) = env::var("UNITS_OUTPUT")
+    {
+        let output = OutputFormat::from_str(&value, true).map_err(|_| {
+            anyhow!("Invalid UNITS_OUTPUT '{value}', expected text, json, yaml, csv or ndjson")
+        })?;
+        args.output = Some(output);
+    }
+    match env::var("UNITS_COLOR").as_deref() {
+        _ if args.no_color => colored::control::set_override(false),
+        Ok("never") => colored::control::set_override(false),
+        Ok("always") => colored::control::set_override(true),
+        Ok("auto") | Err(_) => {}
+        Ok(value) => bail!("Invalid UNITS_COLOR '{value}', expected never, always or auto"),
+    }
+
+    let manager = Manager::new(&args)?;
 
     match args.command {
-        Commands::Status { app_name } => manager.status(app_name),
-        Commands::Install { app_name } => manager.install_apps(app_name),
-        Commands::Uninstall { app_name } => manager.uninstall_apps(app_name),
-        Commands::Logs { app_name } => manager.show_logs(app_name),
+        Commands::Status {
+            app_name,
+            watch,
+            interval,
+            no_alerts,
+            diff_from_last_run,
+            exit_code,
+        } => manager.status(
+            app_name,
+            watch,
+            interval,
+            no_alerts,
+            diff_from_last_run,
+            exit_code,
+        ),
+        Commands::Inspect { app_name } => manager.inspect(app_name),
+        Commands::Summary => manager.summary(),
+        Commands::List { sort_by_security } => manager.list(sort_by_security),
+        Commands::Serve { port, bind } => manager.serve(bind, port),
+        Commands::Graph { output } => manager.graph(output),
+        Commands::Init {
+            app_name,
+            exec_start,
+        } => manager.init(app_name, exec_start),
+        Commands::Register { path, name } => manager.register_app(&path, name),
+        Commands::ImportUnit {
+            unit_path,
+            app_name,
+        } => manager.import_unit(&unit_path, app_name),
+        Commands::Clone {
+            app_name,
+            new_name,
+            dest_repo,
+        } => manager.clone_app(app_name, new_name, dest_repo),
+        Commands::Generate { app_name } => manager.generate(app_name),
+        Commands::Install {
+            app_name,
+            env,
+            continue_on_error,
+            strict,
+            install_dir,
+            preflight,
+            allow_conflicts,
+            health_check,
+        } => manager.install_apps(
+            app_name,
+            InstallOptions {
+                env,
+                continue_on_error,
+                strict,
+                install_dir,
+                preflight,
+                allow_conflicts,
+                health_check,
+            },
+        ),
+        Commands::Uninstall {
+            app_name,
+            keep_dirs,
+            prune,
+            ignore_remove_errors,
+            remove_overrides,
+        } => manager.uninstall_apps(
+            app_name,
+            keep_dirs,
+            prune,
+            ignore_remove_errors,
+            remove_overrides,
+        ),
+        Commands::Reinstall { app_name } => manager.reinstall_apps(app_name),
+        Commands::Resume => manager.resume(),
+        Commands::Undo { list } => manager.undo(list),
+        Commands::Diff { app_name, context } => manager.diff(app_name, context),
+        Commands::Restart { app_name } => manager.restart(app_name),
+        Commands::EnableAll => manager.enable_all_for_boot(),
+        Commands::DisableAll => manager.disable_all(),
+        Commands::ResetFailed { app_name } => manager.reset_failed(app_name),
+        Commands::Unmask { app_name } => manager.unmask(app_name),
+        Commands::Env { app_name } => manager.env(app_name),
+        Commands::Logs {
+            app_name,
+            unit,
+            since_install,
+            invocation,
+        } => manager.show_logs(app_name, unit, since_install, invocation),
+        Commands::ExportLogs {
+            app_name,
+            output,
+            since,
+            until,
+        } => manager.export_logs(app_name, output, since, until),
+        Commands::RotateLogs { app_name } => manager.rotate_logs(app_name),
+        Commands::VacuumLogs {
+            app_name,
+            max_size,
+            max_time,
+        } => manager.vacuum_logs(app_name, max_size, max_time),
+        Commands::ListNamespaces => manager.list_namespaces(),
+        Commands::LogStats { app_name, since } => manager.log_stats(app_name, since),
+        Commands::Test { app_name } => manager.test(app_name),
+        Commands::Resources { app_name, top } => manager.resources(app_name, top),
+        Commands::DiskUsage { app_name } => manager.disk_usage(app_name),
+        Commands::Gc { older_than_days } => manager
+            .gc(time::Duration::from_secs(older_than_days * 24 * 60 * 60))
+            .map(|_| ()),
+        Commands::Doctor => manager.doctor(),
+        Commands::Version { check } => manager.version(check),
+        Commands::ReloadDaemon => manager.reload_daemon(),
+        Commands::Watchdog { app_name } => manager.watchdog(app_name),
+        Commands::NotifyReady { app_name } => manager.notify_ready(app_name),
+        Commands::RunHook { app_name, hook } => manager.run_hook(app_name, hook),
+        Commands::Run { app_name, command } => manager.run(app_name, command),
+        Commands::Security { app_name } => manager.security(app_name),
+        Commands::Override {
+            app_name,
+            property,
+            value,
+            section,
+            restart,
+        } => manager.create_override(app_name, &section, &property, &value, restart),
+        Commands::Edit { app_name, restart } => manager.edit(app_name, restart),
+        Commands::ListOverrides { app_name } => manager.list_overrides(app_name),
+        Commands::ListDropIns { app_name } => manager.list_drop_ins(app_name),
+        Commands::RemoveOverride { app_name } => manager.remove_override(app_name),
     }
 }
+
+fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
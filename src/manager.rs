@@ -1,121 +1,3575 @@
-use anyhow::{Result, anyhow};
-use std::{env, fs, path};
+use anyhow::{Context, Result, anyhow, bail};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::Write;
+use std::{env, ffi, fmt, fs, io, net, path, process, rc, sync, thread, time};
+use walkdir::WalkDir;
 
-use crate::app::App;
-use crate::logging;
+use crate::app::{self, App, AppStatus, LogsOptions, SecurityReport};
+use crate::cli::{Args, ExitCodeMode, HookType, OutputFormat};
+#[cfg(test)]
+use crate::exec::MockCommandRunner;
+use crate::exec::{CommandRunner, RealCommandRunner};
+use crate::manifest::{self, Manifest};
+use crate::progress::{self, InstallPlan, InstallProgress};
+use crate::status_cache::{self, StatusCache};
+use crate::undo::{self, UndoData, UndoRecord, UndoStack};
+use crate::{exec, graph, logging, metrics, timestamp};
+
+const LAST_INSTALL_FILE_NAME: &str = "last_install_time";
+const INSTALL_MTIMES_FILE_NAME: &str = "install_mtimes.json";
+// the first release where `systemctl --user` works without a dbus session
+const MIN_SYSTEMD_VERSION: u32 = 232;
+const MIN_FREE_BYTES: u64 = 1024 * 1024;
+const DEFAULT_RELEASES_URL: &str = "https://api.github.com/repos/agiannif/units/releases/latest";
+const VERSION_CACHE_FILE_NAME: &str = "latest_version_cache.json";
+const VERSION_CACHE_TTL: time::Duration = time::Duration::from_secs(60 * 60);
+const LAST_STATUS_FILE_NAME: &str = "last_status.json";
+const WATCH_HISTORY_LEN: usize = 10;
+const UNIT_EXTENSIONS: [&str; 7] = [
+    "service",
+    "timer",
+    "socket",
+    "path",
+    "mount",
+    "target",
+    "container",
+];
+const SYSTEM_UNIT_DIR: &str = "/etc/systemd/system";
+// how deep an app named explicitly is looked for without --app-depth
+const NESTED_APP_SEARCH_DEPTH: usize = 3;
+// where registered units may already be installed, and whether they're user units
+const UNIT_DIRS: [(&str, bool); 6] = [
+    (SYSTEM_UNIT_DIR, false),
+    ("/etc/systemd/user", true),
+    ("/usr/local/lib/systemd/system", false),
+    ("/usr/local/lib/systemd/user", true),
+    ("/usr/lib/systemd/system", false),
+    ("/usr/lib/systemd/user", true),
+];
 
 pub struct Manager {
     repo_dir: path::PathBuf,
-    force: bool,
+    no_confirm: bool,
+    overwrite: bool,
     dry_run: bool,
+    output: OutputFormat,
+    verbose: bool,
+    porcelain: bool,
+    runner: rc::Rc<dyn CommandRunner>,
+    app_config: Option<path::PathBuf>,
+    status_cache: sync::Arc<sync::Mutex<StatusCache>>,
+    app_depth: Option<usize>,
+    manifest: Option<Manifest>,
+}
+
+// marks apps left out of a bulk operation after an earlier app failed
+#[derive(Debug)]
+struct Skipped;
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "skipped after an earlier failure")
+    }
+}
+
+impl std::error::Error for Skipped {}
+
+#[derive(Serialize)]
+struct InspectRecord {
+    name: String,
+    status: String,
+    app_dir: path::PathBuf,
+    install_location: path::PathBuf,
+    use_user: bool,
+    service_type: String,
+    cgroup_path: Option<String>,
+    restarts: u32,
+    drop_ins: Vec<path::PathBuf>,
+    part_of: Vec<String>,
+    binds_to: Vec<String>,
+    max_restarts_per_hour: Option<u32>,
+    on_start_limit_action: Option<String>,
+    watchdog_usec: Option<u64>,
+    watchdog_missed_count: Option<u64>,
+    fd_store_max: Option<u32>,
+    fd_store_count: Option<u32>,
+    source_changed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exec_start: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CsvStatusRecord {
+    name: String,
+    status: String,
+    install_dir: path::PathBuf,
+    use_user: bool,
+    uptime_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DiskUsageRecord {
+    name: String,
+    // only apps with a log namespace have a journal of their own
+    journal_bytes: Option<u64>,
+    unit_files_bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct Requirement {
+    pub name: String,
+    pub status: RequirementStatus,
+    pub message: String,
+}
+
+#[derive(PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequirementStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+#[derive(Deserialize, Serialize)]
+struct LatestVersionCache {
+    checked_at: u64,
+    latest: String,
+}
+
+#[derive(Default, Serialize)]
+pub struct GcReport {
+    pub files_removed: Vec<path::PathBuf>,
+    pub bytes_freed: u64,
+}
+
+#[derive(Serialize)]
+struct NamespaceRecord {
+    namespace: String,
+    // whether journald currently has a journal for it
+    active: bool,
+    apps: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ResourcesRecord {
+    name: String,
+    status: String,
+    cpu_percent: Option<f64>,
+    memory_bytes: Option<u64>,
+    tasks_current: Option<u32>,
+    tasks_max: Option<u32>,
+    fds: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct NdjsonStatusRecord {
+    timestamp: String,
+    name: String,
+    status: String,
+}
+
+#[derive(Serialize)]
+struct StatusRecord {
+    name: String,
+    status: String,
+    uptime: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exec_start: Option<String>,
+}
+
+#[derive(Default, Serialize)]
+pub struct StatusSummary {
+    pub total: usize,
+    pub running: usize,
+    pub stopped: usize,
+    pub failed: usize,
+    pub not_installed: usize,
+    pub masked: usize,
+    pub restarts: u32,
+}
+
+#[derive(Serialize)]
+pub struct StatusReport {
+    pub name: String,
+    pub status: AppStatus,
+    pub memory_bytes: Option<u64>,
+    pub security_score: Option<f64>,
+}
+
+pub struct StatusDiff<'a> {
+    pub app: &'a App,
+    pub previous: AppStatus,
+    pub current: AppStatus,
+}
+
+pub struct InstallOptions {
+    pub env: Vec<String>,
+    pub continue_on_error: bool,
+    pub strict: bool,
+    pub install_dir: Option<path::PathBuf>,
+    pub preflight: bool,
+    pub allow_conflicts: bool,
+    pub health_check: bool,
+}
+
+pub struct Conflict {
+    pub app_a: String,
+    pub app_b: String,
+    pub file: path::PathBuf,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} and {} both install {}",
+            self.app_a,
+            self.app_b,
+            self.file.display()
+        )
+    }
 }
 
 impl Manager {
-    pub fn new(force: bool, dry_run: bool) -> Result<Self> {
+    pub fn new(args: &Args) -> Result<Self> {
         let exe_path = env::current_exe()?;
-        let repo_dir = exe_path
+        let exe_dir = exe_path
             .parent()
-            .ok_or_else(|| anyhow!("Failed to find script directory"))?
-            .to_path_buf();
+            .ok_or_else(|| anyhow!("Failed to find script directory"))?;
+
+        // the manifest's directory is the repo its app paths are relative to
+        let manifest_path = match &args.manifest {
+            Some(manifest_path) => Some(path::absolute(manifest_path)?),
+            None => Some(exe_dir.join(manifest::MANIFEST_FILE_NAME)).filter(|p| p.exists()),
+        };
+        let (repo_dir, manifest) = match manifest_path {
+            Some(manifest_path) => (
+                manifest_dir(&manifest_path)?,
+                Some(Manifest::load(&manifest_path)?),
+            ),
+            None => (exe_dir.to_path_buf(), None),
+        };
+
+        let status_cache = if args.no_cache {
+            StatusCache::disabled()
+        } else {
+            StatusCache::new(status_cache::DEFAULT_TTL)
+        };
 
         Ok(Manager {
             repo_dir,
-            force,
-            dry_run,
+            // --force is the deprecated spelling of both
+            no_confirm: args.force || args.no_confirm,
+            overwrite: args.force || args.overwrite,
+            dry_run: args.dry_run,
+            output: args.output.unwrap_or(OutputFormat::Text),
+            verbose: args.verbose,
+            porcelain: args.porcelain,
+            runner: rc::Rc::new(RealCommandRunner::new(time::Duration::from_secs(
+                args.systemctl_timeout,
+            ))),
+            app_config: args.app_config.clone(),
+            status_cache: sync::Arc::new(sync::Mutex::new(status_cache)),
+            app_depth: args.app_depth,
+            manifest,
         })
     }
 
-    pub fn status(&self, app_name: Option<String>) -> Result<()> {
-        match app_name {
-            Some(app_name) => {
-                let app = App::new(&app_name)?;
-                let status = app.get_status()?;
-                logging::info(&format!("Status for {}: {status}", app.name))
+    // uses an explicit repo dir instead of the executable's directory
+    #[cfg(test)]
+    pub fn new_for_testing(repo_dir: path::PathBuf, force: bool, dry_run: bool) -> Manager {
+        Manager {
+            repo_dir,
+            no_confirm: force,
+            overwrite: force,
+            dry_run,
+            output: OutputFormat::Text,
+            verbose: false,
+            porcelain: false,
+            runner: rc::Rc::new(MockCommandRunner::default()),
+            app_config: None,
+            status_cache: sync::Arc::new(sync::Mutex::new(StatusCache::disabled())),
+            app_depth: None,
+            manifest: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_runner(mut self, runner: rc::Rc<dyn CommandRunner>) -> Manager {
+        self.runner = runner;
+        self
+    }
+
+    pub fn status(
+        &self,
+        app_name: Option<String>,
+        watch: bool,
+        interval: u64,
+        no_alerts: bool,
+        diff_from_last_run: bool,
+        exit_code: Option<ExitCodeMode>,
+    ) -> Result<()> {
+        let interval = time::Duration::from_secs(interval);
+        if watch && self.output == OutputFormat::Ndjson {
+            return self.stream_status(app_name, interval, !no_alerts);
+        }
+        if watch {
+            return self.watch_status(app_name, interval, !no_alerts);
+        }
+
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+        if apps.is_empty() {
+            logging::warn("No apps found");
+            return Ok(());
+        }
+
+        if diff_from_last_run {
+            self.print_status_changes(&apps)?;
+        } else {
+            self.print_statuses(&apps)?;
+        }
+
+        // a broken state file shouldn't break status itself
+        if let Err(e) = self.save_last_statuses(&apps) {
+            logging::warn(&format!("Failed to save statuses: {e:#}"));
+        }
+
+        if let Some(mode) = exit_code {
+            let mut statuses = Vec::new();
+            for app in &apps {
+                statuses.push(app.get_status()?);
             }
-            None => {
-                let apps = self.discover_apps()?;
-                if apps.is_empty() {
-                    logging::warn("No apps found");
-                    return Ok(());
-                }
+            let healthy = match mode {
+                ExitCodeMode::AnyRunning => statuses.contains(&AppStatus::Running),
+                ExitCodeMode::AllRunning => statuses.iter().all(|s| *s == AppStatus::Running),
+                ExitCodeMode::NoneFailed => !statuses
+                    .iter()
+                    .any(|s| matches!(s, AppStatus::Failed | AppStatus::StartLimitHit)),
+            };
+            if !healthy {
+                process::exit(1);
+            }
+        }
+        Ok(())
+    }
+
+    // json and yaml share the records
+    fn print_structured<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        match self.output {
+            OutputFormat::Yaml => print!("{}", serde_norway::to_string(value)?),
+            _ => println!("{}", serde_json::to_string_pretty(value)?),
+        }
+        Ok(())
+    }
+
+    fn print_status_changes(&self, apps: &[App]) -> Result<()> {
+        let previous = self.load_last_statuses()?;
+        let mut changes = Vec::new();
+        for app in apps {
+            let current = app.get_status()?;
+            let was = match previous.get(&app.name) {
+                Some(status) if *status == current => continue,
+                Some(status) => status.to_string(),
+                None => String::from("(new)"),
+            };
+            changes.push((app.name.clone(), was, current.to_string()));
+        }
+
+        if changes.is_empty() {
+            logging::info("No status changes since the last run");
+            return Ok(());
+        }
+        logging::info(&format!("{:<20}{:<18}{}", "Name", "WAS", "NOW"));
+        for (name, was, now) in changes {
+            logging::info(&format!("{name:<20}{was:<18}{now}"));
+        }
+        Ok(())
+    }
+
+    fn load_last_statuses(&self) -> Result<HashMap<String, AppStatus>> {
+        let path = self
+            .repo_dir
+            .join(undo::STATE_DIR_NAME)
+            .join(LAST_STATUS_FILE_NAME);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn save_last_statuses(&self, apps: &[App]) -> Result<()> {
+        // keep the apps that weren't part of this run
+        let mut statuses = self.load_last_statuses().unwrap_or_default();
+        for app in apps {
+            statuses.insert(app.name.clone(), app.get_status()?);
+        }
+
+        let state_dir = self.repo_dir.join(undo::STATE_DIR_NAME);
+        fs::create_dir_all(&state_dir)
+            .with_context(|| format!("Failed to create {}", state_dir.display()))?;
+        let path = state_dir.join(LAST_STATUS_FILE_NAME);
+        fs::write(&path, serde_json::to_string_pretty(&statuses)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn print_statuses(&self, apps: &[App]) -> Result<()> {
+        if self.porcelain {
+            for app in apps {
+                println!("{}\t{}", app.name, app.get_status()?);
+            }
+            return Ok(());
+        }
 
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let mut records = Vec::new();
+                for app in apps {
+                    let uptime = app
+                        .get_active_since()?
+                        .map(|since| since.elapsed().unwrap_or_default())
+                        .map(iso_duration);
+                    let exec_start = if self.verbose {
+                        app.get_exec_start()?
+                    } else {
+                        None
+                    };
+                    records.push(StatusRecord {
+                        name: app.name.clone(),
+                        status: app.get_status()?.to_string(),
+                        uptime,
+                        exec_start,
+                    });
+                }
+                self.print_structured(&records)?;
+            }
+            OutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                for app in apps {
+                    let uptime_secs = app
+                        .get_active_since()?
+                        .map(|since| since.elapsed().unwrap_or_default().as_secs());
+                    writer.serialize(CsvStatusRecord {
+                        name: app.name.clone(),
+                        status: app.get_status()?.to_string(),
+                        install_dir: app.systemd_dir().to_path_buf(),
+                        use_user: app.use_user(),
+                        uptime_secs,
+                    })?;
+                }
+                writer.flush()?;
+            }
+            OutputFormat::Ndjson => print_ndjson_statuses(apps)?,
+            OutputFormat::Text => {
                 for app in apps {
                     let status = app.get_status()?;
-                    logging::info(&format!("Status for {}: {status}", app.name))
+                    logging::info(&format_status(app, &status)?);
+
+                    if self.verbose
+                        && let Some(since) = app.get_active_since()?
+                    {
+                        let uptime = since.elapsed().unwrap_or_default();
+                        logging::info(&format!(
+                            "{} running for {}",
+                            app.name,
+                            format_duration(uptime)
+                        ));
+                    }
+                    if self.verbose
+                        && matches!(status, AppStatus::Failed | AppStatus::StartLimitHit)
+                        && let Some(reason) = app.failure_reason()?
+                    {
+                        logging::error(&reason);
+                    }
+                    if self.verbose && status != AppStatus::NotInstalled {
+                        logging::info(&format!(
+                            "{} restarted {} times",
+                            app.name,
+                            app.get_restart_count()?
+                        ));
+                    }
                 }
             }
         }
         Ok(())
     }
 
-    pub fn install_apps(&self, app_name: Option<String>) -> Result<()> {
-        match app_name {
-            Some(app_name) => {
-                let app = App::new(&app_name)?;
-                app.install(self.dry_run, self.force)?;
-                logging::success(&format!("App {} installed and started", app.name));
-            }
-            None => {
-                let apps = self.discover_apps()?;
-                if apps.is_empty() {
-                    logging::warn("No apps found");
-                    return Ok(());
-                }
+    pub fn inspect(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let record = InspectRecord {
+            name: app.name.clone(),
+            status: app.get_status()?.to_string(),
+            app_dir: app.app_dir().to_path_buf(),
+            install_location: app.systemd_dir().to_path_buf(),
+            use_user: app.use_user(),
+            service_type: app.service_type()?,
+            cgroup_path: app.get_cgroup_path()?,
+            restarts: app.get_restart_count()?,
+            drop_ins: app.list_drop_ins()?,
+            part_of: app.config().systemd.part_of.clone(),
+            binds_to: app.config().systemd.binds_to.clone(),
+            max_restarts_per_hour: app.config().systemd.max_restarts_per_hour,
+            on_start_limit_action: app
+                .config()
+                .systemd
+                .on_start_limit_action
+                .as_ref()
+                .map(|a| a.to_string()),
+            watchdog_usec: app.get_watchdog_usec()?,
+            watchdog_missed_count: app.get_watchdog_missed_count()?,
+            fd_store_max: Some(app.get_file_descriptor_store_max()?).filter(|max| *max > 0),
+            fd_store_count: app.get_file_descriptor_count()?,
+            source_changed: match self.install_mtimes(&app)? {
+                Some(mtimes) => Some(app.source_changed_since_install(&mtimes)?),
+                None => None,
+            },
+            exec_start: if self.verbose {
+                app.get_exec_start()?
+            } else {
+                None
+            },
+        };
 
-                for app in apps {
-                    logging::info(&format!("Installing app {}", app.name));
-                    app.install(self.dry_run, self.force)?;
-                    logging::success(&format!("App {} installed and started", app.name));
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&record)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                logging::info(&format!("{:<18}{}", "Name", record.name));
+                logging::info(&format!("{:<18}{}", "Status", record.status));
+                logging::info(&format!(
+                    "{:<18}{}",
+                    "App Directory",
+                    record.app_dir.display()
+                ));
+                logging::info(&format!(
+                    "{:<18}{}",
+                    "Install Location",
+                    record.install_location.display()
+                ));
+                logging::info(&format!("{:<18}{}", "User Service", record.use_user));
+                logging::info(&format!("{:<18}{}", "Service Type", record.service_type));
+                logging::info(&format!("{:<18}{}", "Restarts", record.restarts));
+                if let Some(changed) = record.source_changed {
+                    let changed = if changed {
+                        "yes, reinstall to apply"
+                    } else {
+                        "no"
+                    };
+                    logging::info(&format!("{:<18}{changed}", "Source Changed"));
+                }
+                if let Some(cgroup_path) = &record.cgroup_path {
+                    logging::info(&format!("{:<18}{cgroup_path}", "Cgroup Path"));
+                }
+                if !record.part_of.is_empty() {
+                    logging::info(&format!("{:<18}{}", "Part Of", record.part_of.join(" ")));
+                }
+                if !record.binds_to.is_empty() {
+                    logging::info(&format!("{:<18}{}", "Binds To", record.binds_to.join(" ")));
+                }
+                if let Some(max_restarts) = record.max_restarts_per_hour {
+                    let action = match &record.on_start_limit_action {
+                        Some(action) => format!(", then {action}"),
+                        None => String::new(),
+                    };
+                    logging::info(&format!(
+                        "{:<18}{max_restarts} restarts per hour{action}",
+                        "Start Limit"
+                    ));
+                }
+                if let Some(usec) = record.watchdog_usec {
+                    let interval = format_duration(time::Duration::from_micros(usec));
+                    logging::info(&format!("{:<18}every {interval}", "Watchdog"));
+                }
+                if let (Some(max), Some(count)) = (record.fd_store_max, record.fd_store_count) {
+                    logging::info(&format!("{:<18}{count} of {max}", "FD Store"));
+                }
+                for drop_in in &record.drop_ins {
+                    logging::info(&format!("{:<18}{}", "Drop-in", drop_in.display()));
+                }
+                if let Some(exec_start) = &record.exec_start {
+                    logging::info(&format!("{:<18}{exec_start}", "ExecStart"));
+                }
+                if let Some(missed) = record.watchdog_missed_count.filter(|m| *m > 0) {
+                    logging::warn(&format!(
+                        "The watchdog of {} missed {missed} ping(s), is `units watchdog` running?",
+                        record.name
+                    ));
+                }
+                // past 80% the service is close to having descriptors refused
+                if let (Some(max), Some(count)) = (record.fd_store_max, record.fd_store_count)
+                    && u64::from(count) * 5 >= u64::from(max) * 4
+                {
+                    logging::warn(&format!(
+                        "The file descriptor store of {} holds {count} of at most {max}",
+                        record.name
+                    ));
                 }
             }
         }
         Ok(())
     }
 
-    pub fn uninstall_apps(&self, app_name: Option<String>) -> Result<()> {
-        match app_name {
-            Some(app_name) => {
-                let app = App::new(&app_name)?;
-                app.uninstall(self.dry_run, self.force)?;
-                logging::success(&format!("App {} uninstalled", app.name));
+    pub fn init(&self, app_name: String, exec_start: Option<String>) -> Result<()> {
+        let app_dir = self.repo_dir.join(&app_name);
+        if !App::is_path_safe(&self.repo_dir, &app_dir) {
+            bail!("App name {app_name} would put it outside of the repo")
+        }
+        if app_dir.exists() && !self.overwrite {
+            bail!(
+                "Directory {} already exists. Use --overwrite to overwrite.",
+                app_dir.display()
+            )
+        }
+
+        let exec_start = exec_start.unwrap_or_else(|| format!("/usr/bin/{app_name}"));
+        let config = app::new_app_config(
+            &app_name,
+            &app_dir,
+            path::Path::new(SYSTEM_UNIT_DIR),
+            false,
+            Some(&exec_start),
+        )?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would create {} with config:\n{config}",
+                app_dir.display()
+            ));
+            return self.add_to_manifest(&app_name);
+        }
+
+        fs::create_dir_all(&app_dir)
+            .with_context(|| format!("Failed to create {}", app_dir.display()))?;
+        fs::write(app_dir.join(app::CONFIG_FILE_NAME), config)?;
+
+        let app = self.load_app(&app_name)?;
+        let unit_path = app_dir.join(format!("{app_name}.service"));
+        fs::write(&unit_path, app.generate_unit_file()?)
+            .with_context(|| format!("Failed to write {}", unit_path.display()))?;
+
+        self.add_to_manifest(&app_name)?;
+        logging::success(&format!("App {app_name} created in {}", app_dir.display()));
+        Ok(())
+    }
+
+    pub fn register_app(&self, path: &path::Path, name: Option<String>) -> Result<()> {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to find {}", path.display()))?;
+        if !path.is_dir() {
+            bail!("{} is not a directory", path.display())
+        }
+        let app_name = match name {
+            Some(name) => name,
+            None => path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| anyhow!("Failed to get a name from {}", path.display()))?
+                .to_string(),
+        };
+        if !App::is_path_safe(&self.repo_dir, &self.repo_dir.join(&app_name)) {
+            bail!("App name {app_name} would put it outside of the repo")
+        }
+
+        let mut unit_files = Vec::new();
+        for entry in fs::read_dir(&path)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            let is_unit = path::Path::new(&file_name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| UNIT_EXTENSIONS.contains(&e));
+            if is_unit {
+                unit_files.push(file_name);
             }
-            None => {
-                let apps = self.discover_apps()?;
-                if apps.is_empty() {
-                    logging::warn("No apps found");
-                }
+        }
+        if unit_files.is_empty() {
+            bail!("No unit files found in {}", path.display())
+        }
+        unit_files.sort();
 
-                for app in apps {
-                    logging::info(&format!("Uninstalling app {}", app.name));
-                    app.uninstall(self.dry_run, self.force)?;
-                    logging::success(&format!("App {} uninstalled", app.name));
+        let config_path = path.join(app::CONFIG_FILE_NAME);
+        if config_path.exists() && !self.overwrite {
+            bail!(
+                "{} already exists. Use --overwrite to overwrite.",
+                config_path.display()
+            )
+        }
+
+        // already installed files tell us where they belong
+        let mut unit_dirs: Vec<(path::PathBuf, bool)> = env::var("HOME")
+            .map(|home| (path::PathBuf::from(home).join(".config/systemd/user"), true))
+            .into_iter()
+            .collect();
+        unit_dirs.extend(
+            UNIT_DIRS
+                .iter()
+                .map(|(dir, use_user)| (path::PathBuf::from(dir), *use_user)),
+        );
+        let (install_location, use_user) = unit_dirs
+            .into_iter()
+            .find(|(dir, _)| unit_files.iter().any(|f| dir.join(f).exists()))
+            .unwrap_or_else(|| (path::PathBuf::from(SYSTEM_UNIT_DIR), false));
+        let config = app::new_app_config(&app_name, &path, &install_location, use_user, None)?;
+
+        let repo_dir = self.repo_dir.canonicalize()?;
+        let link = if path.parent() == Some(repo_dir.as_path())
+            && path.file_name() == Some(ffi::OsStr::new(&app_name))
+        {
+            None
+        } else {
+            Some(repo_dir.join(&app_name))
+        };
+        if let Some(link) = &link
+            && link.symlink_metadata().is_ok()
+        {
+            bail!("{} already exists", link.display())
+        }
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would register {} as {app_name} with units {}",
+                path.display(),
+                unit_files.join(", ")
+            ));
+            logging::info(&format!(
+                "[DRY RUN] Would write {}:\n{config}",
+                config_path.display()
+            ));
+            if let Some(link) = &link {
+                logging::info(&format!(
+                    "[DRY RUN] Would link {} to {}",
+                    link.display(),
+                    path.display()
+                ));
+            }
+            return self.add_to_manifest(&app_name);
+        }
+
+        fs::write(&config_path, config)
+            .with_context(|| format!("Failed to write {}", config_path.display()))?;
+        logging::info(&format!("Wrote {}", config_path.display()));
+        if let Some(link) = &link {
+            std::os::unix::fs::symlink(&path, link)
+                .with_context(|| format!("Failed to link {}", link.display()))?;
+            logging::info(&format!("Linked {} to {}", link.display(), path.display()));
+        }
+
+        // make sure the result actually loads
+        self.load_app(&app_name)?;
+        self.add_to_manifest(&app_name)?;
+        logging::success(&format!(
+            "Registered {app_name} with units {}",
+            unit_files.join(", ")
+        ));
+        Ok(())
+    }
+
+    pub fn import_unit(&self, unit_path: &path::Path, app_name: Option<String>) -> Result<()> {
+        if !unit_path.is_file() {
+            bail!("{} is not a file", unit_path.display())
+        }
+        let unit_path = path::absolute(unit_path)?;
+        let (Some(file_name), Some(stem)) = (
+            unit_path.file_name().and_then(|n| n.to_str()),
+            unit_path.file_stem().and_then(|n| n.to_str()),
+        ) else {
+            bail!("Failed to get a name from {}", unit_path.display())
+        };
+        let is_unit = unit_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| UNIT_EXTENSIONS.contains(&e));
+        if !is_unit {
+            bail!("{} is not a unit file", unit_path.display())
+        }
+
+        let app_name = app_name.unwrap_or_else(|| stem.to_string());
+        let app_dir = self.repo_dir.join(&app_name);
+        if !App::is_path_safe(&self.repo_dir, &app_dir) {
+            bail!("App name {app_name} would put it outside of the repo")
+        }
+        if app_dir.exists() && !self.overwrite {
+            bail!(
+                "Directory {} already exists. Use --overwrite to overwrite.",
+                app_dir.display()
+            )
+        }
+
+        // the unit stays where it is, so that's where the app installs to
+        let install_location = unit_path
+            .parent()
+            .ok_or_else(|| anyhow!("Failed to get the directory of {}", unit_path.display()))?;
+        let use_user = install_location.ends_with("systemd/user");
+        let config = app::new_app_config(&app_name, &app_dir, install_location, use_user, None)?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would copy {} to {}",
+                unit_path.display(),
+                app_dir.display()
+            ));
+            logging::info(&format!(
+                "[DRY RUN] Would write {}:\n{config}",
+                app_dir.join(app::CONFIG_FILE_NAME).display()
+            ));
+            return self.add_to_manifest(&app_name);
+        }
+
+        let created = !app_dir.exists();
+        let result = (|| -> Result<()> {
+            fs::create_dir_all(&app_dir)
+                .with_context(|| format!("Failed to create {}", app_dir.display()))?;
+            fs::copy(&unit_path, app_dir.join(file_name))
+                .with_context(|| format!("Failed to copy {}", unit_path.display()))?;
+            logging::info(&format!("Copied {}", unit_path.display()));
+            let config_path = app_dir.join(app::CONFIG_FILE_NAME);
+            fs::write(&config_path, &config)
+                .with_context(|| format!("Failed to write {}", config_path.display()))?;
+            logging::info(&format!("Wrote {}", config_path.display()));
+
+            // make sure the result actually loads
+            self.load_app(&app_name)?;
+            self.add_to_manifest(&app_name)
+        })();
+        // a directory that was there before, with --overwrite, is left alone
+        if let Err(e) = result {
+            if created && let Err(e) = fs::remove_dir_all(&app_dir) {
+                logging::warn(&format!("Failed to remove {}: {e}", app_dir.display()));
+            }
+            return Err(e);
+        }
+        logging::success(&format!("Imported {file_name} as {app_name}"));
+        Ok(())
+    }
+
+    // with a manifest, bulk commands only see the apps listed in it
+    fn add_to_manifest(&self, app_name: &str) -> Result<()> {
+        let Some(manifest) = &self.manifest else {
+            return Ok(());
+        };
+        if manifest.find(app_name).is_some() {
+            return Ok(());
+        }
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would add {app_name} to {}",
+                manifest.path.display()
+            ));
+            return Ok(());
+        }
+        manifest.append(app_name, path::Path::new(app_name))?;
+        logging::info(&format!("Added {app_name} to {}", manifest.path.display()));
+        Ok(())
+    }
+
+    pub fn clone_app(
+        &self,
+        app_name: String,
+        new_name: String,
+        dest_repo: Option<path::PathBuf>,
+    ) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let same_repo = dest_repo.is_none();
+        let dest_repo = dest_repo.unwrap_or_else(|| self.repo_dir.clone());
+        if !dest_repo.is_dir() {
+            bail!("{} is not a directory", dest_repo.display())
+        }
+
+        let dest_dir = dest_repo.join(&new_name);
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would copy {} to {}",
+                app.app_dir().display(),
+                dest_dir.display()
+            ));
+        } else {
+            app.clone_to(&dest_repo, Some(&new_name))?;
+        }
+        if same_repo {
+            self.add_to_manifest(&new_name)?;
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+        logging::success(&format!("Cloned {} to {}", app.name, dest_dir.display()));
+        Ok(())
+    }
+
+    pub fn generate(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        print!("{}", app.generate_unit_file()?);
+        Ok(())
+    }
+
+    pub fn status_report(&self) -> Result<Vec<StatusReport>> {
+        let mut reports = Vec::new();
+        for app in self.discover_apps(self.app_depth)? {
+            let status = app.get_status()?;
+            let memory_bytes = match status {
+                AppStatus::Running => app.get_memory_usage()?,
+                _ => None,
+            };
+            // systemd-analyze can only score units systemd knows about
+            let security_score = match status {
+                AppStatus::NotInstalled => None,
+                _ => app.get_security_score().ok(),
+            };
+            reports.push(StatusReport {
+                name: app.name,
+                status,
+                memory_bytes,
+                security_score,
+            });
+        }
+        Ok(reports)
+    }
+
+    pub fn list(&self, sort_by_security: bool) -> Result<()> {
+        let mut reports = self.status_report()?;
+        if sort_by_security {
+            // most exposed first, apps without a score last
+            reports.sort_by(|a, b| {
+                let (a, b) = (
+                    a.security_score.unwrap_or(-1.0),
+                    b.security_score.unwrap_or(-1.0),
+                );
+                b.total_cmp(&a)
+            });
+        }
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&reports)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                logging::info(&format!(
+                    "{:<20}{:<18}{:>10}{:>10}",
+                    "Name", "Status", "Memory", "Exposure"
+                ));
+                for report in &reports {
+                    let memory = match report.memory_bytes {
+                        Some(bytes) => format_bytes(bytes),
+                        None => String::from("-"),
+                    };
+                    let exposure = match report.security_score {
+                        Some(score) => format!("{score:.1}"),
+                        None => String::from("-"),
+                    };
+                    logging::info(&format!(
+                        "{:<20}{:<18}{memory:>10}{exposure:>10}",
+                        report.name,
+                        report.status.to_string()
+                    ));
                 }
             }
         }
         Ok(())
     }
 
-    pub fn show_logs(&self, app_name: String) -> Result<()> {
-        let app = App::new(&app_name)?;
+    pub fn serve(&self, bind: net::IpAddr, port: u16) -> Result<()> {
+        let address = net::SocketAddr::new(bind, port);
+        let server = tiny_http::Server::http(address)
+            .map_err(|e| anyhow!("Failed to start server on {address}: {e}"))?;
+        let content_type =
+            tiny_http::Header::from_bytes("Content-Type", "text/plain; version=0.0.4")
+                .map_err(|_| anyhow!("Failed to build Content-Type header"))?;
 
         logging::info(&format!(
-            "Showing logs for {app_name} (Press Ctrl+C to exit)"
+            "Serving metrics on http://{address}/metrics (Press Ctrl+C to exit)"
         ));
-        app.logs()
+        for request in server.incoming_requests() {
+            let response =
+                if request.method() == &tiny_http::Method::Get && request.url() == "/metrics" {
+                    match self.status_report() {
+                        Ok(reports) => tiny_http::Response::from_string(metrics::render(&reports))
+                            .with_header(content_type.clone()),
+                        Err(e) => {
+                            logging::error(&format!("Failed to collect metrics: {e:#}"));
+                            tiny_http::Response::from_string(format!("{e:#}")).with_status_code(500)
+                        }
+                    }
+                } else {
+                    tiny_http::Response::from_string("Not Found").with_status_code(404)
+                };
+
+            if let Err(e) = request.respond(response) {
+                logging::warn(&format!("Failed to send response: {e}"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn status_summary(&self) -> Result<StatusSummary> {
+        let mut summary = StatusSummary::default();
+
+        for app in self.discover_apps(self.app_depth)? {
+            summary.total += 1;
+            match app.get_status()? {
+                AppStatus::Running => summary.running += 1,
+                AppStatus::Installed | AppStatus::Stopped => summary.stopped += 1,
+                AppStatus::Failed | AppStatus::StartLimitHit => summary.failed += 1,
+                AppStatus::Masked => summary.masked += 1,
+                AppStatus::NotInstalled => {
+                    summary.not_installed += 1;
+                    continue;
+                }
+            }
+            summary.restarts += app.get_restart_count()?;
+        }
+
+        Ok(summary)
+    }
+
+    pub fn summary(&self) -> Result<()> {
+        let summary = self.status_summary()?;
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&summary)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                logging::info(&format!("{:<15}{}", "Total", summary.total));
+                logging::info(&format!("{:<15}{}", "Running", summary.running));
+                logging::info(&format!("{:<15}{}", "Stopped", summary.stopped));
+                logging::info(&format!("{:<15}{}", "Failed", summary.failed));
+                logging::info(&format!("{:<15}{}", "Not Installed", summary.not_installed));
+                logging::info(&format!("{:<15}{}", "Masked", summary.masked));
+                logging::info(&format!("{:<15}{}", "Restarts", summary.restarts));
+            }
+        }
+
+        if summary.failed > 0 {
+            process::exit(1);
+        }
+        Ok(())
     }
 
-    fn discover_apps(&self) -> Result<Vec<App>> {
+    pub fn graph(&self, output: Option<path::PathBuf>) -> Result<()> {
         let mut apps = Vec::new();
+        for app in self.discover_apps(self.app_depth)? {
+            let status = app.get_status()?;
+            apps.push((app, status));
+        }
+
+        let dot = graph::build_dot_graph(&apps);
+        match output {
+            Some(path) => {
+                fs::write(&path, dot)
+                    .with_context(|| format!("Failed to write graph to {}", path.display()))?;
+                logging::success(&format!("Wrote dependency graph to {}", path.display()));
+            }
+            None => print!("{dot}"),
+        }
+
+        Ok(())
+    }
+
+    // appends a line per app on every poll, for log aggregators rather than terminals
+    fn stream_status(
+        &self,
+        app_name: Option<String>,
+        interval: time::Duration,
+        alerts: bool,
+    ) -> Result<()> {
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+        if apps.is_empty() {
+            logging::warn("No apps found");
+            return Ok(());
+        }
+
+        let mut previous: Vec<(&App, AppStatus)> = Vec::new();
+        loop {
+            // statuses are cached for the poll, so printing them doesn't ask systemctl again
+            let mut current = Vec::new();
+            for app in &apps {
+                current.push((app, app.get_status()?));
+            }
+            print_ndjson_statuses(&apps)?;
+
+            if alerts {
+                notify_failed(&compute_diff(&previous, &current));
+            }
+            previous = current;
+            thread::sleep(interval);
+            self.status_cache.lock().unwrap().clear();
+        }
+    }
+
+    fn watch_status(
+        &self,
+        app_name: Option<String>,
+        interval: time::Duration,
+        alerts: bool,
+    ) -> Result<()> {
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+        if apps.is_empty() {
+            logging::warn("No apps found");
+            return Ok(());
+        }
+
+        let mut previous: Vec<(&App, AppStatus)> = Vec::new();
+        let mut history: VecDeque<StatusDiff> = VecDeque::new();
+        // the config each app was last reloaded with, None until it changes
+        let mut reloaded: Vec<Option<app::AppConfig>> = apps.iter().map(|_| None).collect();
 
-        for entry in fs::read_dir(&self.repo_dir)? {
-            let path = entry?.path();
+        loop {
+            let mut current = Vec::new();
+            for app in &apps {
+                current.push((app, app.get_status()?));
+            }
+            let diffs = compute_diff(&previous, &current);
+
+            // clear the screen and move the cursor to the top left
+            print!("\x1B[2J\x1B[H");
+            for (i, (app, status)) in current.iter().enumerate() {
+                let mut line = format_status(app, status)?;
+                if diffs.iter().any(|d| d.app.name == app.name) {
+                    line.push_str(&format!(" {}", "CHANGED".bold()));
+                }
+                logging::info(&line);
+
+                // a config that's mid-edit may not parse, that shouldn't end the watch
+                match app.reload_config() {
+                    Ok(config) if config != *reloaded[i].as_ref().unwrap_or(app.config()) => {
+                        let current = &app.config().systemd;
+                        if config.systemd.install_location != current.install_location
+                            || config.systemd.use_user != current.use_user
+                        {
+                            logging::warn(&format!(
+                                "Config for {} changed, reinstall it for the change to take effect",
+                                app.name
+                            ));
+                        }
+                        reloaded[i] = Some(config);
+                    }
+                    Ok(_) => {}
+                    Err(e) => logging::warn(&format!(
+                        "Failed to reload the config of {}: {e:#}",
+                        app.name
+                    )),
+                }
+
+                // past 80% a fork bomb or thread leak is close to hitting the limit
+                if *status == AppStatus::Running
+                    && let (Some(current), Some(max)) =
+                        (app.get_tasks_current()?, app.get_tasks_max()?)
+                    && u64::from(current) * 5 >= u64::from(max) * 4
+                {
+                    logging::warn(&format!(
+                        "{} is running {current} of at most {max} tasks",
+                        app.name
+                    ));
+                }
+            }
 
-            let app_name = path.file_name().unwrap().to_str().unwrap();
-            if path.is_dir() && !app_name.starts_with('.') {
-                apps.push(App::new(app_name)?);
+            if diffs.iter().any(|d| is_failure(&d.current)) {
+                print!("\x07");
             }
+            if alerts {
+                notify_failed(&diffs);
+            }
+            for diff in diffs {
+                if history.len() == WATCH_HISTORY_LEN {
+                    history.pop_front();
+                }
+                history.push_back(diff);
+            }
+            if !history.is_empty() {
+                println!();
+                logging::info("Recent changes:");
+                for diff in &history {
+                    logging::info(&format!(
+                        "  {}: {} -> {}",
+                        diff.app.name, diff.previous, diff.current
+                    ));
+                }
+            }
+
+            previous = current;
+            thread::sleep(interval);
+            self.status_cache.lock().unwrap().clear();
         }
+    }
 
-        Ok(apps)
+    pub fn install_apps(&self, app_name: Option<String>, options: InstallOptions) -> Result<()> {
+        // installing somewhere else is meant for tests, so make sure it's on purpose
+        if options.install_dir.is_some() && !self.dry_run && !self.no_confirm {
+            bail!("--install-dir can only be used with --dry-run or --no-confirm")
+        }
+        if self.porcelain {
+            logging::set_quiet(true);
+        }
+        let with_install_dir = |mut app: App| {
+            if let Some(dir) = &options.install_dir {
+                app.set_install_dir(dir.clone());
+            }
+            app
+        };
+
+        match app_name {
+            Some(app_name) => {
+                let app = with_install_dir(self.load_app(&app_name)?);
+                self.check_app_conflicts(&app, options.allow_conflicts)?;
+                if options.preflight {
+                    self.preflight(std::slice::from_ref(&app))?;
+                }
+                let footprint = app.compute_install_footprint()?;
+                let result = self.install_app(&app, &options);
+                if self.porcelain {
+                    print_porcelain_result(&app.name, &result);
+                }
+                result?;
+                if self.dry_run {
+                    logging::info(&format!(
+                        "[DRY RUN] Would install {} of unit files",
+                        format_bytes(footprint)
+                    ));
+                }
+            }
+            None => {
+                let apps: Vec<App> = self
+                    .discover_apps(self.app_depth)?
+                    .into_iter()
+                    .map(with_install_dir)
+                    .collect();
+                if apps.is_empty() {
+                    logging::warn("No apps found");
+                    return Ok(());
+                }
+                if options.preflight {
+                    self.preflight(&apps)?;
+                }
+                let footprint = apps
+                    .iter()
+                    .map(|a| a.compute_install_footprint())
+                    .sum::<Result<u64>>()?;
+                let app_count = apps.len();
+
+                let progress = if self.dry_run {
+                    None
+                } else {
+                    let plan = InstallPlan {
+                        apps: apps.iter().map(|a| a.name.clone()).collect(),
+                        completed: Vec::new(),
+                        env: options.env.clone(),
+                        strict: options.strict,
+                        allow_conflicts: options.allow_conflicts,
+                        health_check: options.health_check,
+                        install_dir: options.install_dir.clone(),
+                    };
+                    Some(InstallProgress::start(&self.repo_dir, plan)?)
+                };
+                self.install_bulk(apps, &options, progress)?;
+                if self.dry_run {
+                    logging::info(&format!(
+                        "[DRY RUN] Would install {} of unit files across {app_count} app(s)",
+                        format_bytes(footprint)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn version(&self, check: bool) -> Result<()> {
+        let current = env!("CARGO_PKG_VERSION");
+        if !check {
+            logging::info(&format!("units {current}"));
+            return Ok(());
+        }
+
+        let latest = self.latest_version()?;
+        match parse_version(current).cmp(&parse_version(&latest)) {
+            std::cmp::Ordering::Equal => {
+                logging::success(&format!("units {current} is up to date"));
+            }
+            std::cmp::Ordering::Greater => {
+                logging::info(&format!(
+                    "units {current} is ahead of the latest release {latest}"
+                ));
+            }
+            std::cmp::Ordering::Less => {
+                logging::warn(&format!(
+                    "units {current} is outdated, the latest release is {latest}"
+                ));
+                process::exit(1);
+            }
+        }
+        Ok(())
+    }
+
+    // cached for an hour, the GitHub API is rate limited
+    fn latest_version(&self) -> Result<String> {
+        let cache_path = self
+            .repo_dir
+            .join(undo::STATE_DIR_NAME)
+            .join(VERSION_CACHE_FILE_NAME);
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)?
+            .as_secs();
+        let cached = fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<LatestVersionCache>(&content).ok());
+        if let Some(cache) = cached
+            && now.saturating_sub(cache.checked_at) < VERSION_CACHE_TTL.as_secs()
+        {
+            return Ok(cache.latest);
+        }
+
+        let url = env::var("UNITS_RELEASES_URL").unwrap_or_else(|_| DEFAULT_RELEASES_URL.into());
+        let output = self.runner.run("curl", &["-fsSL", &url]).map_err(|e| {
+            match e.downcast_ref::<io::Error>() {
+                Some(io_error) if io_error.kind() == io::ErrorKind::NotFound => {
+                    anyhow!("curl is needed to check for a new version, install it first")
+                }
+                _ => e,
+            }
+        })?;
+        if !output.status.success() {
+            bail!(
+                "Failed to fetch the latest release from {url}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let release: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("Failed to parse the release from {url}"))?;
+        let latest = release["tag_name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("No tag_name in the release from {url}"))?
+            .trim_start_matches('v')
+            .to_string();
+
+        // a failed cache write only means checking again next time
+        let cache = LatestVersionCache {
+            checked_at: now,
+            latest: latest.clone(),
+        };
+        if let Err(e) = fs::create_dir_all(cache_path.parent().unwrap())
+            .and_then(|()| fs::write(&cache_path, serde_json::to_string(&cache)?))
+        {
+            logging::warn(&format!("Failed to write {}: {e}", cache_path.display()));
+        }
+        Ok(latest)
+    }
+
+    pub fn doctor(&self) -> Result<()> {
+        let requirements = self.check_system_requirements()?;
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&requirements)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                for requirement in &requirements {
+                    let status = match requirement.status {
+                        RequirementStatus::Ok => "OK".green(),
+                        RequirementStatus::Warning => "WARNING".yellow(),
+                        RequirementStatus::Failed => "FAILED".red(),
+                    };
+                    logging::info(&format!(
+                        "{:<22}{:<9}{}",
+                        requirement.name, status, requirement.message
+                    ));
+                }
+            }
+        }
+
+        let failed = requirements
+            .iter()
+            .filter(|r| r.status == RequirementStatus::Failed)
+            .count();
+        if failed > 0 {
+            bail!("{failed} system requirement(s) not met");
+        }
+        Ok(())
+    }
+
+    pub fn check_system_requirements(&self) -> Result<Vec<Requirement>> {
+        let apps = self.discover_apps(self.app_depth)?;
+        self.requirements_for(&apps)
+    }
+
+    fn preflight(&self, apps: &[App]) -> Result<()> {
+        let mut failed = Vec::new();
+        for requirement in self.requirements_for(apps)? {
+            match requirement.status {
+                RequirementStatus::Ok => {}
+                RequirementStatus::Warning => {
+                    logging::warn(&format!("{}: {}", requirement.name, requirement.message))
+                }
+                RequirementStatus::Failed => {
+                    failed.push(format!("{}: {}", requirement.name, requirement.message))
+                }
+            }
+        }
+        if !failed.is_empty() {
+            bail!("Preflight checks failed:\n  {}", failed.join("\n  "));
+        }
+        Ok(())
+    }
+
+    fn requirements_for(&self, apps: &[App]) -> Result<Vec<Requirement>> {
+        let requirement = |name: &str, status, message: String| Requirement {
+            name: name.to_string(),
+            status,
+            message,
+        };
+        let mut requirements = Vec::new();
+
+        // e.g. "systemd 252 (252.38-1~deb12u1)"
+        let version = self
+            .runner
+            .run("systemctl", &["--version"])
+            .ok()
+            .filter(|o| o.status.success())
+            .and_then(|o| {
+                let stdout = String::from_utf8_lossy(&o.stdout).to_string();
+                stdout.split_whitespace().nth(1)?.parse::<u32>().ok()
+            });
+        requirements.push(match version {
+            Some(v) if v >= MIN_SYSTEMD_VERSION => requirement(
+                "systemd version",
+                RequirementStatus::Ok,
+                format!("systemd {v}"),
+            ),
+            Some(v) => requirement(
+                "systemd version",
+                RequirementStatus::Failed,
+                format!("systemd {v} is older than {MIN_SYSTEMD_VERSION}"),
+            ),
+            None => requirement(
+                "systemd version",
+                RequirementStatus::Failed,
+                String::from("Couldn't read the version from systemctl --version"),
+            ),
+        });
+
+        for program in ["systemctl", "journalctl"] {
+            let runs = self
+                .runner
+                .run(program, &["--version"])
+                .is_ok_and(|o| o.status.success());
+            requirements.push(if runs {
+                requirement(program, RequirementStatus::Ok, String::from("Executable"))
+            } else {
+                requirement(
+                    program,
+                    RequirementStatus::Failed,
+                    format!("Couldn't run {program} --version"),
+                )
+            });
+        }
+
+        // only version --check needs it, so it's not worth failing an install over
+        let curl_runs = self
+            .runner
+            .run("curl", &["--version"])
+            .is_ok_and(|o| o.status.success());
+        requirements.push(if curl_runs {
+            requirement("curl", RequirementStatus::Ok, String::from("Executable"))
+        } else {
+            requirement(
+                "curl",
+                RequirementStatus::Warning,
+                String::from("Couldn't run curl --version, version --check needs it"),
+            )
+        });
+
+        let init = fs::read_to_string("/proc/1/comm").unwrap_or_default();
+        let init = init.trim();
+        requirements.push(if init == "systemd" {
+            requirement(
+                "service manager",
+                RequirementStatus::Ok,
+                String::from("PID 1 is systemd"),
+            )
+        } else {
+            requirement(
+                "service manager",
+                RequirementStatus::Failed,
+                format!("PID 1 is '{init}', not systemd"),
+            )
+        });
+
+        let mut install_dirs: Vec<&path::Path> = apps.iter().map(|a| a.systemd_dir()).collect();
+        install_dirs.sort();
+        install_dirs.dedup();
+        for dir in install_dirs {
+            requirements.push(match self.free_bytes(dir) {
+                Some(free) if free > MIN_FREE_BYTES => requirement(
+                    "disk space",
+                    RequirementStatus::Ok,
+                    format!("{} free in {}", format_bytes(free), dir.display()),
+                ),
+                Some(free) => requirement(
+                    "disk space",
+                    RequirementStatus::Failed,
+                    format!("Only {} free in {}", format_bytes(free), dir.display()),
+                ),
+                None => requirement(
+                    "disk space",
+                    RequirementStatus::Warning,
+                    format!("Couldn't check the free space in {}", dir.display()),
+                ),
+            });
+        }
+
+        requirements.push(if progress::is_locked(&self.repo_dir)? {
+            requirement(
+                "other units processes",
+                RequirementStatus::Failed,
+                String::from("Another install is in progress"),
+            )
+        } else {
+            requirement(
+                "other units processes",
+                RequirementStatus::Ok,
+                String::from("None running"),
+            )
+        });
+
+        Ok(requirements)
+    }
+
+    // the install location may not exist yet, so check the closest directory that does
+    fn free_bytes(&self, dir: &path::Path) -> Option<u64> {
+        let existing = dir.ancestors().find(|d| d.exists())?;
+        let output = self
+            .runner
+            .run("df", &["-Pk", &existing.to_string_lossy()])
+            .ok()
+            .filter(|o| o.status.success())?;
+
+        // Filesystem 1024-blocks Used Available Capacity Mounted on
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let available: u64 = stdout
+            .lines()
+            .nth(1)?
+            .split_whitespace()
+            .nth(3)?
+            .parse()
+            .ok()?;
+        Some(available * 1024)
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        if self.porcelain {
+            logging::set_quiet(true);
+        }
+        let Some(progress) = InstallProgress::resume(&self.repo_dir)? else {
+            logging::info("No interrupted install to resume");
+            return Ok(());
+        };
+
+        let remaining = progress.remaining();
+        let mut apps = Vec::new();
+        for app_name in &remaining {
+            let mut app = self.load_app(app_name)?;
+            if let Some(dir) = &progress.plan.install_dir {
+                app.set_install_dir(dir.clone());
+            }
+            apps.push(app);
+        }
+        logging::info(&format!("Resuming install of {}", remaining.join(", ")));
+
+        let options = InstallOptions {
+            env: progress.plan.env.clone(),
+            continue_on_error: false,
+            strict: progress.plan.strict,
+            install_dir: progress.plan.install_dir.clone(),
+            preflight: false,
+            allow_conflicts: progress.plan.allow_conflicts,
+            health_check: progress.plan.health_check,
+        };
+        let progress = if self.dry_run { None } else { Some(progress) };
+        self.install_bulk(apps, &options, progress)
+    }
+
+    // progress is recorded after every app, so an interrupted install can be resumed
+    fn install_bulk(
+        &self,
+        apps: Vec<App>,
+        options: &InstallOptions,
+        mut progress: Option<InstallProgress>,
+    ) -> Result<()> {
+        // the app installed last would silently replace the other's files
+        let conflicts = self.check_conflicts(&apps)?;
+        if !conflicts.is_empty() && !options.allow_conflicts {
+            let details: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+            bail!(
+                "Conflicting unit files found. Use --allow-conflicts to install anyway.\n{}",
+                details.join("\n")
+            )
+        }
+        for conflict in &conflicts {
+            logging::warn(&format!("Conflict: {conflict}"));
+        }
+
+        let mut results = Vec::new();
+        let mut failed = false;
+        for app in apps {
+            if failed && !options.continue_on_error {
+                let result = Err(anyhow::Error::new(Skipped));
+                if self.porcelain {
+                    print_porcelain_result(&app.name, &result);
+                }
+                results.push((app.name, result));
+                continue;
+            }
+
+            logging::info(&format!("Installing app {}", app.name));
+            let scope = logging::with_prefix(&app.name);
+            let result = self.install_app(&app, options);
+            drop(scope);
+            match &result {
+                Ok(()) => {
+                    if let Some(progress) = &mut progress {
+                        progress.complete(&app.name)?;
+                    }
+                }
+                Err(e) => {
+                    logging::error(&format!("Failed to install {}: {e:#}", app.name));
+                    failed = true;
+                }
+            }
+            if self.porcelain {
+                print_porcelain_result(&app.name, &result);
+            }
+            results.push((app.name, result));
+        }
+
+        Manager::print_summary(&results);
+        if failed {
+            if progress.is_some() {
+                logging::info(
+                    "Fix the failure and run `units resume` to install the remaining apps",
+                );
+            }
+            bail!("Failed to install all apps")
+        }
+        if let Some(progress) = progress {
+            progress.finish()?;
+        }
+        Ok(())
+    }
+
+    pub fn print_summary(results: &[(String, Result<()>)]) {
+        let (mut installed, mut skipped, mut failed) = (0, 0, 0);
+
+        logging::info("Summary:");
+        for (name, result) in results {
+            let line = match result {
+                Ok(()) => {
+                    installed += 1;
+                    format!("  {name:<20}{}", "OK".green())
+                }
+                Err(e) if e.is::<Skipped>() => {
+                    skipped += 1;
+                    format!("  {name:<20}{}", "SKIPPED".yellow())
+                }
+                Err(e) => {
+                    failed += 1;
+                    format!("  {name:<20}{} {e:#}", "FAILED".red())
+                }
+            };
+            logging::info(&line);
+        }
+        logging::info(&format!(
+            "{installed} installed, {skipped} skipped, {failed} failed"
+        ));
+    }
+
+    fn install_app(&self, app: &App, options: &InstallOptions) -> Result<()> {
+        if self.verbose {
+            for (file, size) in app.install_file_sizes()? {
+                let file = file.strip_prefix(app.app_dir()).unwrap_or(&file);
+                logging::info(&format!("{:<40}{:>10}", file.display(), format_bytes(size)));
+            }
+        }
+        app.install(
+            self.dry_run,
+            self.overwrite,
+            &options.env,
+            options.strict,
+            options.health_check,
+        )?;
+        self.status_cache.lock().unwrap().clear();
+        // a copy somewhere else isn't the app's installation, so it's neither
+        // recorded nor undoable
+        if app.install_dir_overridden() {
+            logging::success(&format!(
+                "App {} installed to {}",
+                app.name,
+                app.systemd_dir().display()
+            ));
+            return Ok(());
+        }
+        logging::success(&format!("App {} installed and started", app.name));
+
+        if !self.dry_run {
+            self.record_install_time(app)?;
+            self.push_undo(
+                "install",
+                app,
+                UndoData::Install {
+                    files: app.target_paths()?,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn uninstall_apps(
+        &self,
+        app_name: Option<String>,
+        keep_dirs: bool,
+        prune: bool,
+        ignore_remove_errors: bool,
+        remove_overrides: bool,
+    ) -> Result<()> {
+        let bulk = app_name.is_none();
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app_exact(&app_name)?],
+            None => {
+                let apps = self.discover_apps(self.app_depth)?;
+                if apps.is_empty() {
+                    logging::warn("No apps found");
+                }
+                apps
+            }
+        };
+
+        if self.porcelain {
+            logging::set_quiet(true);
+        }
+        for app in apps {
+            if bulk {
+                logging::info(&format!("Uninstalling app {}", app.name));
+            }
+            let scope = bulk.then(|| logging::with_prefix(&app.name));
+            let result = self.uninstall_app(
+                &app,
+                keep_dirs,
+                prune,
+                ignore_remove_errors,
+                remove_overrides,
+            );
+            drop(scope);
+            if self.porcelain {
+                let outcome = match &result {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(anyhow!("cancelled")),
+                    Err(e) => Err(anyhow!("{e:#}")),
+                };
+                print_porcelain_result(&app.name, &outcome);
+            }
+            result?;
+        }
+        Ok(())
+    }
+
+    // returns false when the uninstall was cancelled
+    fn uninstall_app(
+        &self,
+        app: &App,
+        keep_dirs: bool,
+        prune: bool,
+        ignore_remove_errors: bool,
+        remove_overrides: bool,
+    ) -> Result<bool> {
+        let backup = if self.dry_run {
+            None
+        } else {
+            Some(self.backup_installed_files(app)?)
+        };
+        let result = app
+            .uninstall(
+                self.dry_run,
+                self.no_confirm,
+                keep_dirs,
+                ignore_remove_errors,
+                remove_overrides,
+            )
+            .and_then(|uninstalled| {
+                if uninstalled && let Some((backup_dir, files)) = &backup {
+                    let undo_data = UndoData::Uninstall {
+                        backup_dir: backup_dir.clone(),
+                        files: files.clone(),
+                        systemd_dir: Some(app.systemd_dir().to_path_buf()),
+                        use_user: app.use_user(),
+                        units: app.unit_names(),
+                    };
+                    self.push_undo("uninstall", app, undo_data)?;
+                }
+                Ok(uninstalled)
+            });
+        // the backup is only kept when there's an undo record pointing at it
+        if !matches!(result, Ok(true))
+            && let Some((backup_dir, _)) = &backup
+        {
+            remove_backup(backup_dir);
+        }
+        if !result? {
+            return Ok(false);
+        }
+        self.status_cache.lock().unwrap().clear();
+        logging::success(&format!("App {} uninstalled", app.name));
+
+        if prune {
+            self.prune_app(app)?;
+        }
+        Ok(true)
+    }
+
+    // e.g. after an OS upgrade, to pick up changes in how systemd reads unit files
+    pub fn reinstall_apps(&self, app_name: Option<String>) -> Result<()> {
+        let apps = match app_name {
+            Some(app_name) => {
+                let app = self.load_app_exact(&app_name)?;
+                if app.get_status()? == AppStatus::NotInstalled {
+                    bail!("App {} is not installed, use `units install`", app.name);
+                }
+                vec![app]
+            }
+            None => {
+                let mut installed = Vec::new();
+                for app in self.discover_apps(self.app_depth)? {
+                    if app.get_status()? != AppStatus::NotInstalled {
+                        installed.push(app);
+                    }
+                }
+                installed
+            }
+        };
+        if apps.is_empty() {
+            logging::warn("No installed apps found");
+            return Ok(());
+        }
+
+        let names: Vec<&str> = apps.iter().map(|a| a.name.as_str()).collect();
+        logging::warn(&format!(
+            "Reinstalling stops and restarts {}",
+            names.join(", ")
+        ));
+        if !self.dry_run && !self.no_confirm {
+            let confirmation = dialoguer::Confirm::new()
+                .with_prompt(format!("Reinstall {} app(s)?", apps.len()))
+                .default(false)
+                .interact()
+                .context("Reinstall confirmation failed")?;
+            if !confirmation {
+                logging::info("Reinstall cancelled");
+                return Ok(());
+            }
+        }
+
+        for app in &apps {
+            logging::info(&format!("Reinstalling app {}", app.name));
+            let _scope = logging::with_prefix(&app.name);
+            self.reinstall_app(app)?;
+        }
+        Ok(())
+    }
+
+    // the installed files and drop-ins are backed up first and put back if
+    // the uninstall or install fails
+    fn reinstall_app(&self, app: &App) -> Result<()> {
+        let env = app.installed_env()?;
+        if self.dry_run {
+            app.uninstall(true, true, false, false, false)?;
+            return app.install(true, true, &env, false, false);
+        }
+
+        let (backup_dir, files) = self.backup_installed_files(app)?;
+        let result = app
+            .uninstall(false, true, false, false, false)
+            .and_then(|_| {
+                self.status_cache.lock().unwrap().clear();
+                app.install(false, true, &env, false, false)
+            });
+
+        if let Err(e) = result {
+            // the backup is kept if it couldn't be put back
+            restore_backup(&backup_dir, app.systemd_dir(), &files)
+                .and_then(|()| app.daemon_reload())
+                .and_then(|()| app.restart())
+                .with_context(|| {
+                    format!(
+                        "Failed to restore the previous unit files, they're backed up in {}",
+                        backup_dir.display()
+                    )
+                })?;
+            remove_backup(&backup_dir);
+            self.status_cache.lock().unwrap().clear();
+            return Err(e.context(format!(
+                "Failed to reinstall {}, restored the previous unit files",
+                app.name
+            )));
+        }
+
+        fs::remove_dir_all(&backup_dir)?;
+        self.status_cache.lock().unwrap().clear();
+        self.record_install_time(app)?;
+        logging::success(&format!("App {} reinstalled", app.name));
+        Ok(())
+    }
+
+    pub fn undo(&self, list: bool) -> Result<()> {
+        let mut stack = UndoStack::load(&self.repo_dir)?;
+
+        if list {
+            if stack.records.is_empty() {
+                logging::info("Nothing to undo");
+            }
+            for record in stack.records.iter().rev() {
+                logging::info(&format!(
+                    "{} {} {}",
+                    record.timestamp, record.operation, record.app_name
+                ));
+            }
+            return Ok(());
+        }
+
+        let Some(record) = stack.pop() else {
+            logging::info("Nothing to undo");
+            return Ok(());
+        };
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would undo {} of {}",
+                record.operation, record.app_name
+            ));
+            return Ok(());
+        }
+
+        match &record.undo_data {
+            UndoData::Install { .. } => {
+                let app = self.load_app(&record.app_name)?;
+                app.uninstall(false, true, false, false, false)?;
+            }
+            UndoData::Uninstall {
+                backup_dir,
+                files,
+                systemd_dir: Some(systemd_dir),
+                use_user,
+                units,
+            } => {
+                restore_backup(backup_dir, systemd_dir, files)?;
+                self.systemctl(*use_user, &["daemon-reload"])?;
+                for unit in units {
+                    self.systemctl(*use_user, &["restart", unit])?;
+                }
+                fs::remove_dir_all(backup_dir)?;
+            }
+            UndoData::Uninstall {
+                backup_dir,
+                files,
+                systemd_dir: None,
+                ..
+            } => {
+                let app = self.load_app(&record.app_name)?;
+                restore_backup(backup_dir, app.systemd_dir(), files)?;
+                app.daemon_reload()?;
+                app.restart()?;
+                fs::remove_dir_all(backup_dir)?;
+            }
+        }
+
+        stack.save(&self.repo_dir)?;
+        logging::success(&format!(
+            "Undid {} of {}",
+            record.operation, record.app_name
+        ));
+        Ok(())
+    }
+
+    fn systemctl(&self, use_user: bool, args: &[&str]) -> Result<()> {
+        let mut args = args.to_vec();
+        if use_user {
+            args.insert(0, "--user");
+        }
+        let output = self.runner.run("systemctl", &args)?;
+        if !output.status.success() {
+            bail!(
+                "Failed to run systemctl {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
+    fn record_install_time(&self, app: &App) -> Result<()> {
+        let state_dir = self.repo_dir.join(undo::STATE_DIR_NAME).join(&app.name);
+        fs::create_dir_all(&state_dir)
+            .with_context(|| format!("Failed to create {}", state_dir.display()))?;
+        fs::write(
+            state_dir.join(LAST_INSTALL_FILE_NAME),
+            timestamp::now_rfc3339(),
+        )?;
+
+        // later edits to the installed files shouldn't hide changed sources
+        let mut mtimes = BTreeMap::new();
+        for (file, modified) in app.installed_file_mtimes()? {
+            mtimes.insert(file, modified.duration_since(time::UNIX_EPOCH)?.as_secs());
+        }
+        let mtimes_path = state_dir.join(INSTALL_MTIMES_FILE_NAME);
+        fs::write(&mtimes_path, serde_json::to_string_pretty(&mtimes)?)
+            .with_context(|| format!("Failed to write {}", mtimes_path.display()))?;
+        Ok(())
+    }
+
+    // None for apps installed before their mtimes were recorded
+    fn install_mtimes(
+        &self,
+        app: &App,
+    ) -> Result<Option<HashMap<path::PathBuf, time::SystemTime>>> {
+        let path = self
+            .repo_dir
+            .join(undo::STATE_DIR_NAME)
+            .join(&app.name)
+            .join(INSTALL_MTIMES_FILE_NAME);
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let mtimes: HashMap<path::PathBuf, u64> = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(
+            mtimes
+                .into_iter()
+                .map(|(file, secs)| (file, time::UNIX_EPOCH + time::Duration::from_secs(secs)))
+                .collect(),
+        ))
+    }
+
+    fn last_install_time(&self, app: &App) -> Option<String> {
+        let path = self
+            .repo_dir
+            .join(undo::STATE_DIR_NAME)
+            .join(&app.name)
+            .join(LAST_INSTALL_FILE_NAME);
+        fs::read_to_string(path).ok()
+    }
+
+    fn push_undo(&self, operation: &str, app: &App, undo_data: UndoData) -> Result<()> {
+        let mut stack = UndoStack::load(&self.repo_dir)?;
+        stack.push(UndoRecord {
+            operation: operation.to_string(),
+            app_name: app.name.clone(),
+            timestamp: timestamp::now_rfc3339(),
+            undo_data,
+        });
+        stack.save(&self.repo_dir)
+    }
+
+    // returns the backup directory and the files copied into it
+    fn backup_installed_files(&self, app: &App) -> Result<(path::PathBuf, Vec<path::PathBuf>)> {
+        let stamp = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)?
+            .as_secs();
+        let backup_dir = self
+            .repo_dir
+            .join(undo::STATE_DIR_NAME)
+            .join("backups")
+            .join(format!("{}-{stamp}", app.name));
+
+        let files = app.installed_files()?;
+        let result = files.iter().try_for_each(|file| {
+            let backup = backup_dir.join(file.strip_prefix(app.systemd_dir())?);
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(file, &backup)
+                .with_context(|| format!("Failed to back up {}", file.display()))?;
+            Ok(())
+        });
+        if let Err(e) = result.and_then(|()| Ok(fs::create_dir_all(&backup_dir)?)) {
+            remove_backup(&backup_dir);
+            return Err(e);
+        }
+
+        Ok((backup_dir, files))
+    }
+
+    fn prune_app(&self, app: &App) -> Result<()> {
+        let app_dir = app
+            .app_dir()
+            .canonicalize()
+            .with_context(|| format!("Failed to find {}", app.app_dir().display()))?;
+        let repo_dir = self.repo_dir.canonicalize()?;
+        if app_dir == repo_dir || !app_dir.starts_with(&repo_dir) {
+            bail!(
+                "Refusing to remove {}, it is not inside {}",
+                app_dir.display(),
+                repo_dir.display()
+            );
+        }
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would remove directory {}",
+                app_dir.display()
+            ));
+            if app.config().systemd.create_service_user {
+                logging::info(&format!(
+                    "[DRY RUN] Would offer to delete service user {}",
+                    app.service_user()
+                ));
+            }
+            return Ok(());
+        }
+
+        if !self.no_confirm {
+            let confirmation = dialoguer::Confirm::new()
+                .with_prompt(format!(
+                    "Permanently delete {}? This cannot be undone",
+                    app_dir.display()
+                ))
+                .default(false)
+                .interact()
+                .context("Prune confirmation failed")?;
+
+            if !confirmation {
+                logging::info("Prune cancelled");
+                return Ok(());
+            }
+        }
+
+        fs::remove_dir_all(&app_dir)
+            .with_context(|| format!("Failed to remove {}", app_dir.display()))?;
+        logging::info(&format!("Removed directory {}", app_dir.display()));
+
+        if app.config().systemd.create_service_user {
+            let delete_user = self.no_confirm
+                || dialoguer::Confirm::new()
+                    .with_prompt(format!("Also delete service user {}?", app.service_user()))
+                    .default(false)
+                    .interact()
+                    .context("Service user confirmation failed")?;
+            if delete_user {
+                app.delete_service_account()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn diff(&self, app_name: String, context: usize) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let diff = app.diff_installed(context)?;
+        if diff.is_empty() {
+            logging::info(&format!("Installed files for {} are up to date", app.name));
+            return Ok(());
+        }
+
+        for line in diff.lines() {
+            println!("{}", colorize_diff_line(line));
+        }
+        Ok(())
+    }
+
+    pub fn restart(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            if app.config().systemd.restart_mode == app::RestartMode::StopStart {
+                app.stop_then_start(true)?;
+            } else {
+                logging::info(&format!("[DRY RUN] Would restart {}", app.name));
+            }
+            return Ok(());
+        }
+
+        app.restart()?;
+        self.status_cache.lock().unwrap().clear();
+        logging::success(&format!("App {} restarted", app.name));
+        Ok(())
+    }
+
+    pub fn reset_failed(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would reset the failed state of {}",
+                app.name
+            ));
+            return Ok(());
+        }
+
+        app.reset_failed()?;
+        self.status_cache.lock().unwrap().clear();
+        logging::success(&format!("Reset the failed state of {}", app.name));
+        Ok(())
+    }
+
+    pub fn unmask(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!("[DRY RUN] Would unmask {}.service", app.name));
+            return Ok(());
+        }
+
+        app.unmask()?;
+        logging::success(&format!("Unmasked {}.service", app.name));
+        Ok(())
+    }
+
+    pub fn enable_all_for_boot(&self) -> Result<()> {
+        self.set_all_enabled(true)
+    }
+
+    pub fn disable_all(&self) -> Result<()> {
+        self.set_all_enabled(false)
+    }
+
+    fn set_all_enabled(&self, enable: bool) -> Result<()> {
+        let (verb, done, logged) = if enable {
+            ("enable", "enabled", "Enabled")
+        } else {
+            ("disable", "disabled", "Disabled")
+        };
+        let (mut changed, mut already, mut is_static, mut not_installed) = (0, 0, 0, 0);
+        for app in self.discover_apps(self.app_depth)? {
+            if app.get_status()? == AppStatus::NotInstalled {
+                not_installed += 1;
+                continue;
+            }
+            // units without an [Install] section can't be enabled or disabled
+            if app.is_static()? {
+                is_static += 1;
+                continue;
+            }
+            if app.is_enabled()? == enable {
+                already += 1;
+                continue;
+            }
+
+            if self.dry_run {
+                logging::info(&format!("[DRY RUN] Would {verb} {}.service", app.name));
+            } else {
+                if enable {
+                    app.enable()?;
+                } else {
+                    app.disable()?;
+                }
+                logging::info(&format!("{logged} {}.service", app.name));
+            }
+            changed += 1;
+        }
+        self.status_cache.lock().unwrap().clear();
+
+        let summary = format!(
+            "{changed} newly {done}, {already} already {done}, {is_static} static, {not_installed} not installed"
+        );
+        if self.dry_run {
+            logging::info(&format!("[DRY RUN] {summary}"));
+        } else {
+            logging::success(&summary);
+        }
+        Ok(())
+    }
+
+    pub fn env(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let environment = app.get_environment()?;
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => {
+                let variables: BTreeMap<&str, &str> = environment
+                    .iter()
+                    .map(|var| var.split_once('=').unwrap_or((var, "")))
+                    .collect();
+                self.print_structured(&variables)?;
+            }
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                if app.get_status()? != AppStatus::Running {
+                    logging::warn(&format!(
+                        "{} is not running, its environment may differ once started",
+                        app.name
+                    ));
+                }
+                for var in &environment {
+                    logging::info(var);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn show_logs(
+        &self,
+        app_name: String,
+        unit: Option<String>,
+        since_install: bool,
+        invocation: Option<String>,
+    ) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        let mut since = None;
+        if since_install {
+            since = self
+                .last_install_time(&app)
+                .and_then(|installed| timestamp::rfc3339_to_journal(installed.trim()));
+            if since.is_none() {
+                logging::warn(&format!(
+                    "No install record found for {app_name}, showing all logs"
+                ));
+            }
+        }
+
+        if let OutputFormat::Json | OutputFormat::Yaml = self.output {
+            let records =
+                app.log_records(unit.as_deref(), since.as_deref(), invocation.as_deref())?;
+            return self.print_structured(&records);
+        }
+
+        if invocation.is_none()
+            && let Ok(Some(current)) = app.get_invocation_id()
+        {
+            logging::info(&format!("Current invocation: {current}"));
+        }
+        logging::info(&format!(
+            "Showing logs for {app_name} (Press Ctrl+C to exit)"
+        ));
+        app.logs(unit.as_deref(), since.as_deref(), invocation.as_deref())
+    }
+
+    pub fn export_logs(
+        &self,
+        app_name: String,
+        output: path::PathBuf,
+        since: Option<String>,
+        until: Option<String>,
+    ) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would export the logs of {app_name} to {}",
+                output.display()
+            ));
+            return Ok(());
+        }
+        if output.exists() && !self.overwrite {
+            bail!(
+                "File {} already exists. Use --overwrite to overwrite.",
+                output.display()
+            )
+        }
+
+        app.export_journal_to_file(&output, &LogsOptions { since, until })?;
+        logging::success(&format!(
+            "Exported the logs of {app_name} to {}",
+            output.display()
+        ));
+        Ok(())
+    }
+
+    pub fn rotate_logs(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        require_log_namespace(&app)?;
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would rotate the journal and vacuum the archived entries of {app_name}"
+            ));
+            return Ok(());
+        }
+        app.rotate_logs()?;
+        logging::success(&format!("Rotated the logs of {app_name}"));
+        Ok(())
+    }
+
+    pub fn vacuum_logs(
+        &self,
+        app_name: String,
+        max_size: Option<String>,
+        max_time: Option<String>,
+    ) -> Result<()> {
+        if max_size.is_none() && max_time.is_none() {
+            bail!("Nothing to vacuum, pass --max-size and/or --max-time")
+        }
+        let app = self.load_app(&app_name)?;
+        require_log_namespace(&app)?;
+        if self.dry_run {
+            let limits: Vec<&str> = [&max_size, &max_time]
+                .into_iter()
+                .filter_map(|limit| limit.as_deref())
+                .collect();
+            logging::info(&format!(
+                "[DRY RUN] Would vacuum the archived logs of {app_name}, keeping at most {}",
+                limits.join(" and ")
+            ));
+            return Ok(());
+        }
+        app.vacuum_logs(max_size.as_deref(), max_time.as_deref())?;
+        logging::success(&format!("Vacuumed the logs of {app_name}"));
+        Ok(())
+    }
+
+    pub fn log_stats(&self, app_name: Option<String>, since: Option<String>) -> Result<()> {
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+
+        for app in apps {
+            let stats = app.get_log_priority_stats(since.as_deref())?;
+            let total: u32 = stats.values().sum();
+
+            logging::info(&format!(
+                "Log priorities for {} ({total} messages)",
+                app.name
+            ));
+            logging::info(&format!(
+                "{:<10}{:>8}{:>12}",
+                "Priority", "Count", "Percentage"
+            ));
+            for priority in app::LOG_PRIORITIES {
+                let count = stats.get(priority).copied().unwrap_or(0);
+                let percentage = if total == 0 {
+                    0.0
+                } else {
+                    f64::from(count) * 100.0 / f64::from(total)
+                };
+                let line = format!("{priority:<10}{count:>8}{percentage:>11.1}%");
+                if count > 0 && matches!(priority, "emerg" | "alert" | "crit") {
+                    logging::info(&line.red().to_string());
+                } else {
+                    logging::info(&line);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn list_namespaces(&self) -> Result<()> {
+        let output = self.runner.run("journalctl", &["--list-namespaces"])?;
+        if !output.status.success() {
+            bail!(
+                "Failed to list journal namespaces: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut namespaces: BTreeMap<String, NamespaceRecord> = BTreeMap::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let namespace = line.trim();
+            if namespace.is_empty() {
+                continue;
+            }
+            namespaces.insert(
+                namespace.to_string(),
+                NamespaceRecord {
+                    namespace: namespace.to_string(),
+                    active: true,
+                    apps: Vec::new(),
+                },
+            );
+        }
+        for app in self.discover_apps(self.app_depth)? {
+            let Some(namespace) = app.log_namespace() else {
+                continue;
+            };
+            namespaces
+                .entry(namespace.to_string())
+                .or_insert_with(|| NamespaceRecord {
+                    namespace: namespace.to_string(),
+                    active: false,
+                    apps: Vec::new(),
+                })
+                .apps
+                .push(app.name.clone());
+        }
+        let records: Vec<NamespaceRecord> = namespaces.into_values().collect();
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&records)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                if records.is_empty() {
+                    logging::info("No journal namespaces found");
+                    return Ok(());
+                }
+                logging::info(&format!("{:<20}{:<10}{}", "Namespace", "Active", "Apps"));
+                for record in &records {
+                    let apps = if record.apps.is_empty() {
+                        String::from("-")
+                    } else {
+                        record.apps.join(", ")
+                    };
+                    let active = if record.active { "yes" } else { "no" };
+                    logging::info(&format!("{:<20}{active:<10}{apps}", record.namespace));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn disk_usage(&self, app_name: Option<String>) -> Result<()> {
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+
+        let mut records = Vec::new();
+        for app in &apps {
+            records.push(DiskUsageRecord {
+                name: app.name.clone(),
+                journal_bytes: app.get_journal_size()?,
+                unit_files_bytes: app.get_unit_files_size()?,
+            });
+        }
+        records
+            .sort_by_key(|r| std::cmp::Reverse(r.journal_bytes.unwrap_or(0) + r.unit_files_bytes));
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&records)?,
+            OutputFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(io::stdout());
+                for record in &records {
+                    writer.serialize(record)?;
+                }
+                writer.flush()?;
+            }
+            OutputFormat::Ndjson => {
+                let mut stdout = io::stdout().lock();
+                for record in &records {
+                    writeln!(stdout, "{}", serde_json::to_string(record)?)?;
+                }
+            }
+            OutputFormat::Text => {
+                logging::info(&format!(
+                    "{:<20}{:>14}{:>18}",
+                    "Name", "Journal Size", "Unit Files Size"
+                ));
+                for record in &records {
+                    logging::info(&format!(
+                        "{:<20}{:>14}{:>18}",
+                        record.name,
+                        record.journal_bytes.map_or(String::from("-"), format_bytes),
+                        format_bytes(record.unit_files_bytes)
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // removes old uninstall backups, and the state of apps that are gone from
+    // the repo. the undo stack and other shared state files are kept
+    pub fn gc(&self, older_than: time::Duration) -> Result<GcReport> {
+        let state_dir = self.repo_dir.join(undo::STATE_DIR_NAME);
+        let cutoff = time::SystemTime::now()
+            .checked_sub(older_than)
+            .unwrap_or(time::UNIX_EPOCH);
+
+        let mut candidate_dirs = vec![state_dir.join("backups")];
+        if state_dir.exists() {
+            for entry in fs::read_dir(&state_dir)? {
+                let path = entry?.path();
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                // ambiguous names may still belong to an app, so they're kept
+                let removed = self
+                    .resolve_app(&name, true)
+                    .is_ok_and(|(_, dir)| !dir.is_dir());
+                if path.is_dir() && name != "backups" && removed {
+                    candidate_dirs.push(path);
+                }
+            }
+        }
+
+        let mut report = GcReport::default();
+        let removal = (|| -> Result<()> {
+            for dir in candidate_dirs.iter().filter(|d| d.exists()) {
+                for entry in WalkDir::new(dir).sort_by_file_name() {
+                    let entry = entry?;
+                    let metadata = entry.metadata()?;
+                    if !metadata.is_file() || metadata.modified()? >= cutoff {
+                        continue;
+                    }
+
+                    if self.dry_run {
+                        logging::info(&format!(
+                            "[DRY RUN] Would remove {} ({})",
+                            entry.path().display(),
+                            format_bytes(metadata.len())
+                        ));
+                    } else {
+                        fs::remove_file(entry.path()).with_context(|| {
+                            format!("Failed to remove {}", entry.path().display())
+                        })?;
+                    }
+                    report.files_removed.push(entry.path().to_path_buf());
+                    report.bytes_freed += metadata.len();
+                }
+            }
+            Ok(())
+        })();
+
+        // runs even if a removal failed, so the stack never points at
+        // backups that are already partly gone
+        if !self.dry_run && !report.files_removed.is_empty() {
+            // directories emptied by the removal go too, children before parents
+            let dirs: BTreeSet<&path::Path> = report
+                .files_removed
+                .iter()
+                .flat_map(|file| file.ancestors().skip(1))
+                .filter(|dir| dir.starts_with(&state_dir) && *dir != state_dir)
+                .collect();
+            for dir in dirs.iter().rev() {
+                let empty = fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none());
+                if empty && let Err(e) = fs::remove_dir(dir) {
+                    logging::warn(&format!("Failed to remove {}: {e}", dir.display()));
+                }
+            }
+
+            // an uninstall can't be undone once any of its backup is gone
+            let mut stack = UndoStack::load(&self.repo_dir)?;
+            stack.records.retain(|record| match &record.undo_data {
+                UndoData::Uninstall { backup_dir, .. } => {
+                    backup_dir.exists()
+                        && !report
+                            .files_removed
+                            .iter()
+                            .any(|file| file.starts_with(backup_dir))
+                }
+                UndoData::Install { .. } => true,
+            });
+            stack.save(&self.repo_dir)?;
+        }
+        removal?;
+
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&report)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson if self.dry_run => {
+                logging::info(&format!(
+                    "[DRY RUN] Would remove {} file(s), freeing {}",
+                    report.files_removed.len(),
+                    format_bytes(report.bytes_freed)
+                ));
+            }
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                logging::success(&format!(
+                    "Removed {} file(s), freed {}",
+                    report.files_removed.len(),
+                    format_bytes(report.bytes_freed)
+                ));
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn resources(&self, app_name: Option<String>, top: bool) -> Result<()> {
+        // a refreshing screen can't be parsed
+        if top && self.output != OutputFormat::Text {
+            bail!("--top can only be used with --output text")
+        }
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+        if apps.is_empty() {
+            logging::warn("No apps found");
+            return Ok(());
+        }
+
+        loop {
+            let mut records = Vec::new();
+            for app in &apps {
+                // statuses change between refreshes
+                self.status_cache.lock().unwrap().clear();
+                let status = app.get_status()?;
+                let (cpu_percent, memory_bytes, tasks_current) = match status {
+                    AppStatus::Running => (
+                        app.get_cpu_usage_percent()?,
+                        app.get_memory_usage()?,
+                        app.get_tasks_current()?,
+                    ),
+                    _ => (None, None, None),
+                };
+                records.push(ResourcesRecord {
+                    name: app.name.clone(),
+                    status: status.to_string(),
+                    cpu_percent,
+                    memory_bytes,
+                    tasks_current,
+                    tasks_max: app.get_tasks_max()?,
+                    fds: app.get_file_descriptor_count()?,
+                });
+            }
+
+            if !top {
+                return self.print_resources(&records);
+            }
+
+            // busiest first, apps without a reading last
+            records.sort_by(|a, b| {
+                let (a, b) = (a.cpu_percent.unwrap_or(-1.0), b.cpu_percent.unwrap_or(-1.0));
+                b.total_cmp(&a)
+            });
+            // clear the screen and move the cursor to the top left
+            print!("\x1B[2J\x1B[H");
+            self.print_resources(&records)?;
+            thread::sleep(time::Duration::from_secs(1));
+        }
+    }
+
+    fn print_resources(&self, records: &[ResourcesRecord]) -> Result<()> {
+        match self.output {
+            OutputFormat::Json | OutputFormat::Yaml => self.print_structured(&records)?,
+            OutputFormat::Text | OutputFormat::Csv | OutputFormat::Ndjson => {
+                logging::info(&format!(
+                    "{:<20}{:<18}{:>8}{:>10}{:>12}{:>6}",
+                    "Name", "Status", "CPU", "Memory", "Tasks", "FDs"
+                ));
+                for record in records {
+                    let cpu = match record.cpu_percent {
+                        Some(percent) => format!("{percent:.1}%"),
+                        None => String::from("-"),
+                    };
+                    let memory = match record.memory_bytes {
+                        Some(bytes) => format_bytes(bytes),
+                        None => String::from("-"),
+                    };
+                    let tasks = match (record.tasks_current, record.tasks_max) {
+                        (Some(current), Some(max)) => format!("{current}/{max}"),
+                        (Some(current), None) => current.to_string(),
+                        (None, _) => String::from("-"),
+                    };
+                    let fds = match record.fds {
+                        Some(fds) => fds.to_string(),
+                        None => String::from("-"),
+                    };
+                    logging::info(&format!(
+                        "{:<20}{:<18}{cpu:>8}{memory:>10}{tasks:>12}{fds:>6}",
+                        record.name, record.status
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn test(&self, app_name: Option<String>) -> Result<()> {
+        let apps = match app_name {
+            Some(app_name) => vec![self.load_app(&app_name)?],
+            None => self.discover_apps(self.app_depth)?,
+        };
+
+        let (mut passed, mut warned, mut failed) = (0, 0, 0);
+        for app in &apps {
+            for report in app.test_configuration()? {
+                let unit = report
+                    .unit
+                    .strip_prefix(app.app_dir())
+                    .unwrap_or(&report.unit);
+                if !report.errors.is_empty() {
+                    failed += 1;
+                    logging::error(&format!("{}/{}", app.name, unit.display()));
+                } else if !report.warnings.is_empty() {
+                    warned += 1;
+                    logging::warn(&format!("{}/{}", app.name, unit.display()));
+                } else {
+                    passed += 1;
+                    logging::success(&format!("{}/{}", app.name, unit.display()));
+                }
+                for message in report.errors.iter().chain(&report.warnings) {
+                    logging::info(&format!("  {message}"));
+                }
+            }
+        }
+
+        logging::info(&format!(
+            "{passed} units passed, {warned} had warnings, {failed} had errors"
+        ));
+        if failed > 0 {
+            process::exit(1);
+        }
+        Ok(())
+    }
+
+    pub fn reload_daemon(&self) -> Result<()> {
+        let apps = self.discover_apps(self.app_depth)?;
+
+        if self.dry_run {
+            logging::info("[DRY RUN] Would reload systemd");
+            return Ok(());
+        }
+
+        // reload each systemd instance once
+        let mut reloaded = Vec::new();
+        for app in &apps {
+            if !reloaded.contains(&app.use_user()) {
+                app.daemon_reload()?;
+                reloaded.push(app.use_user());
+            }
+        }
+
+        let mut stale = 0;
+        for app in &apps {
+            if app.detect_stale_unit()? {
+                logging::warn(&format!("App {} is still stale after reload", app.name));
+                stale += 1;
+            }
+        }
+        if stale > 0 {
+            bail!("{stale} app(s) still need a daemon reload");
+        }
+
+        logging::success("Systemd reloaded");
+        Ok(())
+    }
+
+    pub fn watchdog(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let handle = app.enable_watchdog()?;
+
+        logging::info(&format!(
+            "Sending watchdog pings for {} (Press Ctrl+C to exit)",
+            app.name
+        ));
+        handle
+            .join()
+            .map_err(|_| anyhow!("Watchdog thread for {} panicked", app.name))
+    }
+
+    pub fn notify_ready(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would notify systemd that {} is ready",
+                app.name
+            ));
+            return Ok(());
+        }
+
+        app.notify_systemd_ready()?;
+        logging::success(&format!("Notified systemd that {} is ready", app.name));
+        Ok(())
+    }
+
+    pub fn run_hook(&self, app_name: String, hook: HookType) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!("[DRY RUN] Would run {hook} hook for {}", app.name));
+            return Ok(());
+        }
+
+        app.run_hook(hook)?;
+        logging::success(&format!("Ran {hook} hook for {}", app.name));
+        Ok(())
+    }
+
+    pub fn run(&self, app_name: String, command: Vec<String>) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would run '{}' as {}-transient",
+                command.join(" "),
+                app.name
+            ));
+            return Ok(());
+        }
+
+        app.transient_run(&command)
+    }
+
+    pub fn security(&self, app_name: Option<String>) -> Result<()> {
+        match app_name {
+            Some(app_name) => {
+                let app = self.load_app(&app_name)?;
+                let report = app.security_score()?;
+                logging::info(&format_security(&app.name, &report));
+                for warning in &report.warnings {
+                    logging::warn(warning);
+                }
+            }
+            None => {
+                let mut reports = Vec::new();
+                for app in self.discover_apps(self.app_depth)? {
+                    if app.get_status()? == AppStatus::NotInstalled {
+                        continue;
+                    }
+                    let report = app.security_score()?;
+                    reports.push((app.name, report));
+                }
+
+                if reports.is_empty() {
+                    logging::warn("No installed apps found");
+                    return Ok(());
+                }
+
+                reports.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+                for (name, report) in reports {
+                    logging::info(&format!(
+                        "{} ({} warnings)",
+                        format_security(&name, &report),
+                        report.warnings.len()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn create_override(
+        &self,
+        app_name: String,
+        section: &str,
+        property: &str,
+        value: &str,
+        restart: bool,
+    ) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would add [{section}] {property}={value} to {}.service",
+                app.name
+            ));
+            if restart {
+                logging::info(&format!("[DRY RUN] Would restart {}.service", app.name));
+            }
+            return Ok(());
+        }
+
+        app.create_override(section, property, value)?;
+        if restart {
+            app.restart()?;
+        }
+        logging::success(&format!("Override added to {}", app.name));
+        Ok(())
+    }
+
+    pub fn edit(&self, app_name: String, restart: bool) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let override_path = app.override_path();
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would open {} in an editor and reload systemd",
+                override_path.display()
+            ));
+            if restart {
+                logging::info(&format!("[DRY RUN] Would restart {}.service", app.name));
+            }
+            return Ok(());
+        }
+
+        if !override_path.exists() {
+            let content = app.generate_override_file()?;
+            if let Some(parent) = override_path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            fs::write(&override_path, content)
+                .with_context(|| format!("Failed to write {}", override_path.display()))?;
+        }
+        exec::open_in_editor(&override_path)?;
+
+        let report = app.verify_installed_service()?;
+        for warning in &report.warnings {
+            logging::warn(warning);
+        }
+        if !report.errors.is_empty() {
+            for error in &report.errors {
+                logging::error(error);
+            }
+            bail!(
+                "{} is invalid, not reloading. Run `units edit {}` to fix it",
+                override_path.display(),
+                app.name
+            );
+        }
+
+        app.daemon_reload()?;
+        if restart {
+            app.restart()?;
+        }
+        logging::success(&format!("Override for {} updated", app.name));
+        Ok(())
+    }
+
+    pub fn list_overrides(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let overrides = app.list_drop_ins()?;
+        if overrides.is_empty() {
+            logging::info(&format!("No drop-in files found for {}", app.name));
+            return Ok(());
+        }
+
+        for file in overrides {
+            logging::info(&file.display().to_string());
+        }
+        Ok(())
+    }
+
+    pub fn list_drop_ins(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+        let drop_ins = app.list_drop_ins()?;
+        if drop_ins.is_empty() {
+            logging::info(&format!("No drop-in files found for {}", app.name));
+            return Ok(());
+        }
+
+        for file in drop_ins {
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            logging::info(&format!("# {}", file.display()));
+            print!("{content}");
+        }
+        Ok(())
+    }
+
+    pub fn remove_override(&self, app_name: String) -> Result<()> {
+        let app = self.load_app(&app_name)?;
+
+        if self.dry_run {
+            logging::info(&format!(
+                "[DRY RUN] Would remove the override for {}.service",
+                app.name
+            ));
+            return Ok(());
+        }
+
+        app.remove_override()?;
+        logging::success(&format!("Override removed from {}", app.name));
+        Ok(())
+    }
+
+    pub fn check_conflicts(&self, apps: &[App]) -> Result<Vec<Conflict>> {
+        let targets = apps
+            .iter()
+            .map(|app| app.get_target_paths())
+            .collect::<Result<Vec<_>>>()?;
+        let mut conflicts = Vec::new();
+
+        for (i, app_a) in apps.iter().enumerate() {
+            for (j, app_b) in apps.iter().enumerate().skip(i + 1) {
+                let mut shared: Vec<_> = targets[i].intersection(&targets[j]).collect();
+                shared.sort();
+                for file in shared {
+                    conflicts.push(Conflict {
+                        app_a: app_a.name.clone(),
+                        app_b: app_b.name.clone(),
+                        file: file.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    // the same check install_bulk does, against the apps that aren't being installed
+    fn check_app_conflicts(&self, app: &App, allow_conflicts: bool) -> Result<()> {
+        if self.app_config.is_some() {
+            return Ok(());
+        }
+        // a broken app elsewhere in the repo shouldn't block this install
+        let others = match self.discover_apps(self.app_depth) {
+            Ok(others) => others,
+            Err(e) => {
+                logging::warn(&format!("Failed to check for conflicting apps: {e:#}"));
+                return Ok(());
+            }
+        };
+        let conflicting = app.detect_conflicting_apps(&others)?;
+        if conflicting.is_empty() {
+            return Ok(());
+        }
+
+        let message = format!(
+            "{} installs the same unit files as {}",
+            app.name,
+            conflicting.join(", ")
+        );
+        if !allow_conflicts {
+            bail!("{message}. Use --allow-conflicts to install anyway.")
+        }
+        logging::warn(&format!("Conflict: {message}"));
+        Ok(())
+    }
+
+    fn load_app(&self, app_name: &str) -> Result<App> {
+        self.load_resolved_app(app_name, false)
+    }
+
+    // for commands that remove things, a prefix of the name isn't enough
+    fn load_app_exact(&self, app_name: &str) -> Result<App> {
+        self.load_resolved_app(app_name, true)
+    }
+
+    fn load_resolved_app(&self, app_name: &str, exact: bool) -> Result<App> {
+        let (app_name, app_dir) = self.resolve_app(app_name, exact)?;
+        App::new(
+            &app_name,
+            &self.repo_dir,
+            app_dir,
+            self.app_config.clone(),
+            self.runner.clone(),
+            self.status_cache.clone(),
+        )
+    }
+
+    // finds an app by its exact name, then nested anywhere in the repo, then
+    // by a unique prefix of a top level app's name
+    fn resolve_app(&self, app_name: &str, exact: bool) -> Result<(String, path::PathBuf)> {
+        if let Some(app) = self.manifest.as_ref().and_then(|m| m.find(app_name)) {
+            return Ok((app.name.clone(), self.repo_dir.join(&app.path)));
+        }
+
+        let app_dir = self.repo_dir.join(app_name);
+        if app_dir.is_dir() || self.app_config.is_some() {
+            return Ok((app_name.to_string(), app_dir));
+        }
+
+        let mut nested = Vec::new();
+        let mut prefixed = Vec::new();
+        let walker = WalkDir::new(&self.repo_dir)
+            .min_depth(1)
+            .max_depth(self.app_depth.unwrap_or(NESTED_APP_SEARCH_DEPTH))
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'));
+        for entry in walker {
+            let entry = entry?;
+            if !entry.file_type().is_dir() || !entry.path().join(app::CONFIG_FILE_NAME).exists() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.depth() > 1 && name == app_name {
+                nested.push(entry.into_path());
+            } else if entry.depth() == 1 && !exact && name.starts_with(app_name) {
+                prefixed.push(entry.into_path());
+            }
+        }
+
+        let is_prefix = nested.is_empty();
+        let candidates = if is_prefix { prefixed } else { nested };
+        match candidates.as_slice() {
+            // let App::new report the missing config
+            [] => Ok((app_name.to_string(), app_dir)),
+            [app_dir] => {
+                let name = app_dir.file_name().unwrap().to_string_lossy().to_string();
+                if is_prefix {
+                    logging::info(&format!("Using app {name} for {app_name}"));
+                }
+                Ok((name, app_dir.clone()))
+            }
+            _ => bail!(
+                "App name {app_name} is ambiguous, it could be any of:\n  {}",
+                candidates
+                    .iter()
+                    .map(|c| c
+                        .strip_prefix(&self.repo_dir)
+                        .unwrap_or(c)
+                        .display()
+                        .to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n  ")
+            ),
+        }
+    }
+
+    fn discover_apps(&self, max_depth: Option<usize>) -> Result<Vec<App>> {
+        if self.app_config.is_some() {
+            bail!("--app-config can only be used with a single app");
+        }
+        if let Some(manifest) = &self.manifest {
+            return manifest
+                .apps
+                .iter()
+                .map(|app| self.load_app(&app.name))
+                .collect();
+        }
+
+        let mut apps = Vec::new();
+
+        // only direct children of the repo are apps, deeper levels are only
+        // scanned to warn about misplaced ones
+        let walker = WalkDir::new(&self.repo_dir)
+            .min_depth(1)
+            .max_depth(max_depth.unwrap_or(1))
+            .into_iter()
+            .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'));
+        for entry in walker {
+            let entry = entry?;
+            // registered apps outside the repo are symlinked into it
+            if !entry.path().is_dir() {
+                continue;
+            }
+
+            let path = entry.path();
+            if entry.depth() > 1 {
+                if path.join(app::CONFIG_FILE_NAME).exists() {
+                    logging::warn(&format!(
+                        "Ignoring {}, nested apps are only found when named explicitly",
+                        path.display()
+                    ));
+                }
+                continue;
+            }
+
+            let app_name = entry.file_name().to_string_lossy();
+            apps.push(self.load_app(&app_name)?);
+        }
+
+        Ok(apps)
+    }
+}
+
+fn manifest_dir(manifest_path: &path::Path) -> Result<path::PathBuf> {
+    manifest_path
+        .parent()
+        .map(path::Path::to_path_buf)
+        .ok_or_else(|| {
+            anyhow!(
+                "Failed to find the directory of {}",
+                manifest_path.display()
+            )
+        })
+}
+
+// copies backed up files back to where they were installed
+fn restore_backup(
+    backup_dir: &path::Path,
+    systemd_dir: &path::Path,
+    files: &[path::PathBuf],
+) -> Result<()> {
+    for file in files {
+        let relative = file.strip_prefix(systemd_dir)?;
+        let backup = backup_dir.join(relative);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&backup, file).with_context(|| {
+            format!(
+                "Failed to restore {} from {}",
+                file.display(),
+                backup.display()
+            )
+        })?;
+        logging::info(&format!("Restored {}", file.display()));
+    }
+    Ok(())
+}
+
+fn remove_backup(backup_dir: &path::Path) {
+    if backup_dir.exists()
+        && let Err(e) = fs::remove_dir_all(backup_dir)
+    {
+        logging::warn(&format!("Failed to remove {}: {e}", backup_dir.display()));
+    }
+}
+
+// colors are dropped by colored itself when --no-color or UNITS_COLOR=never is set
+fn colorize_diff_line(line: &str) -> String {
+    if line.starts_with("@@") {
+        line.cyan().to_string()
+    } else if line.starts_with('+') {
+        line.green().to_string()
+    } else if line.starts_with('-') {
+        line.red().to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+// one line per app that scripts can rely on, see --porcelain
+fn print_porcelain_result(app_name: &str, result: &Result<()>) {
+    match result {
+        Ok(()) => println!("{app_name}\tok"),
+        Err(e) => {
+            let message = format!("{e:#}").replace(['\n', '\t'], " ");
+            println!("{app_name}\terr\t{message}");
+        }
+    }
+}
+
+// journald vacuums whole journal files, so without a namespace of its own
+// the app's logs can't be vacuumed without dropping every other service's
+fn require_log_namespace(app: &App) -> Result<()> {
+    if app.log_namespace().is_none() {
+        bail!(
+            "{} has no systemd.log_namespace_id, vacuuming would drop the archived logs of every service",
+            app.name
+        );
+    }
+    Ok(())
+}
+
+fn print_ndjson_statuses(apps: &[App]) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    for app in apps {
+        let record = NdjsonStatusRecord {
+            timestamp: timestamp::now_rfc3339(),
+            name: app.name.clone(),
+            status: app.get_status()?.to_string(),
+        };
+        writeln!(stdout, "{}", serde_json::to_string(&record)?)?;
+    }
+    // piped output is block buffered, flush so every poll shows up right away
+    stdout.flush()?;
+    Ok(())
+}
+
+fn format_status(app: &App, status: &AppStatus) -> Result<String> {
+    if *status == AppStatus::Masked {
+        return Ok(format!(
+            "Status for {}: {} (run `units unmask {}` to allow it to start)",
+            app.name,
+            status.to_string().dimmed().strikethrough(),
+            app.name
+        ));
+    }
+    if *status == AppStatus::StartLimitHit {
+        return Ok(format!(
+            "Status for {}: {} (run `units reset-failed {}` before starting it again)",
+            app.name,
+            status.to_string().red().bold(),
+            app.name
+        ));
+    }
+
+    let mut line = format!("Status for {}: {status}", app.name);
+    if *status != AppStatus::NotInstalled && app.detect_stale_unit()? {
+        line.push_str(&format!(" {}", "[STALE]".yellow()));
+    }
+    Ok(line)
+}
+
+fn is_failure(status: &AppStatus) -> bool {
+    matches!(status, AppStatus::Failed | AppStatus::StartLimitHit)
+}
+
+fn notify_failed(diffs: &[StatusDiff]) {
+    for diff in diffs.iter().filter(|d| is_failure(&d.current)) {
+        // a broken alert shouldn't stop the watch
+        if let Err(e) = diff.app.notify(&diff.current) {
+            logging::warn(&format!("{e:#}"));
+        }
+    }
+}
+
+pub fn compute_diff<'a>(
+    previous: &[(&'a App, AppStatus)],
+    current: &[(&'a App, AppStatus)],
+) -> Vec<StatusDiff<'a>> {
+    let mut diffs = Vec::new();
+    for (app, status) in current {
+        let Some((_, old)) = previous.iter().find(|(a, _)| a.name == app.name) else {
+            continue;
+        };
+        if old != status {
+            diffs.push(StatusDiff {
+                app,
+                previous: old.clone(),
+                current: status.clone(),
+            });
+        }
+    }
+    diffs
+}
+
+pub fn format_duration(d: time::Duration) -> String {
+    let secs = d.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs % 3600 / 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+// "1.2.3" and "v1.2.3" compare as [1, 2, 3], pre-release suffixes are ignored
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split(['.', '-', '+'])
+        .map_while(|part| part.parse().ok())
+        .collect()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes}B")
+    } else {
+        format!("{size:.1}{}", UNITS[unit])
+    }
+}
+
+fn iso_duration(d: time::Duration) -> String {
+    let secs = d.as_secs();
+    format!("PT{}H{}M{}S", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+fn format_security(name: &str, report: &SecurityReport) -> String {
+    let score = format!("{:.1} {}", report.score, report.exposure);
+    let score = if report.score < 4.0 {
+        score.green()
+    } else if report.score <= 7.0 {
+        score.yellow()
+    } else {
+        score.red()
+    };
+    format!("Security for {name}: {score}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::MockCommandRunner;
+
+    // a repo with a "demo" app, its unit file already copied to the install location
+    fn test_repo(test_name: &str) -> path::PathBuf {
+        let repo_dir = env::temp_dir().join(format!("units-{test_name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&repo_dir);
+        let app_dir = repo_dir.join("demo");
+        let install_dir = repo_dir.join("installed");
+        fs::create_dir_all(&app_dir).unwrap();
+        fs::create_dir_all(&install_dir).unwrap();
+        fs::write(
+            app_dir.join(app::CONFIG_FILE_NAME),
+            format!(
+                "[systemd]\ninstall_location = {}\nuse_user = false\n",
+                toml::Value::from(install_dir.to_str().unwrap())
+            ),
+        )
+        .unwrap();
+        let unit = "[Service]\nExecStart=/bin/true\n";
+        fs::write(app_dir.join("demo.service"), unit).unwrap();
+        fs::write(install_dir.join("demo.service"), unit).unwrap();
+        repo_dir
+    }
+
+    fn test_manager(repo_dir: &path::Path, runner: &rc::Rc<MockCommandRunner>) -> Manager {
+        Manager::new_for_testing(repo_dir.to_path_buf(), true, false).with_runner(runner.clone())
+    }
+
+    #[test]
+    fn status_is_running_when_the_service_is_active() {
+        let repo_dir = test_repo("status-running");
+        let runner = rc::Rc::new(MockCommandRunner::default());
+        runner.expect(
+            "env TZ=UTC systemctl show --property=LoadState --value demo.service",
+            0,
+            "loaded\n",
+        );
+        runner.expect("sudo systemctl is-active --quiet demo.service", 0, "");
+
+        let app = test_manager(&repo_dir, &runner).load_app("demo").unwrap();
+        assert_eq!(app.get_status().unwrap(), AppStatus::Running);
+        assert!(runner.is_done());
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+
+    #[test]
+    fn status_is_stopped_when_an_enabled_service_is_inactive() {
+        let repo_dir = test_repo("status-stopped");
+        let runner = rc::Rc::new(MockCommandRunner::default());
+        runner.expect(
+            "env TZ=UTC systemctl show --property=LoadState --value demo.service",
+            0,
+            "loaded\n",
+        );
+        runner.expect("sudo systemctl is-active --quiet demo.service", 3, "");
+        runner.expect("sudo systemctl is-failed --quiet demo.service", 1, "");
+        runner.expect("sudo systemctl is-enabled --quiet demo.service", 0, "");
+
+        let app = test_manager(&repo_dir, &runner).load_app("demo").unwrap();
+        assert_eq!(app.get_status().unwrap(), AppStatus::Stopped);
+        assert!(runner.is_done());
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+
+    #[test]
+    fn status_is_start_limit_hit_from_the_result() {
+        let repo_dir = test_repo("status-start-limit");
+        let runner = rc::Rc::new(MockCommandRunner::default());
+        runner.expect(
+            "env TZ=UTC systemctl show --property=LoadState --value demo.service",
+            0,
+            "loaded\n",
+        );
+        runner.expect("sudo systemctl is-active --quiet demo.service", 3, "");
+        runner.expect("sudo systemctl is-failed --quiet demo.service", 0, "");
+        runner.expect(
+            "env TZ=UTC systemctl show --property=Result --value demo.service",
+            0,
+            "start-limit-hit\n",
+        );
+
+        let app = test_manager(&repo_dir, &runner).load_app("demo").unwrap();
+        assert_eq!(app.get_status().unwrap(), AppStatus::StartLimitHit);
+        assert!(runner.is_done());
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+
+    #[test]
+    fn diff_shows_changes_since_install() {
+        let repo_dir = test_repo("diff");
+        let source = repo_dir.join("demo/demo.service");
+        let installed = repo_dir.join("installed/demo.service");
+        fs::write(
+            &source,
+            include_str!("../tests/fixtures/diff/source.service"),
+        )
+        .unwrap();
+        fs::write(
+            &installed,
+            include_str!("../tests/fixtures/diff/installed.service"),
+        )
+        .unwrap();
+        let runner = rc::Rc::new(MockCommandRunner::default());
+
+        let app = test_manager(&repo_dir, &runner).load_app("demo").unwrap();
+        let expected = format!(
+            "--- {}\n+++ {}\n@@ -2,7 +2,7 @@\n Description=demo\n \n [Service]\n-ExecStart=/usr/bin/demo\n+ExecStart=/usr/bin/demo --verbose\n Restart=always\n \n [Install]\n",
+            installed.display(),
+            source.display()
+        );
+        assert_eq!(app.diff_installed(3).unwrap(), expected);
+        assert!(
+            app.diff_installed(0)
+                .unwrap()
+                .contains("@@ -5 +5 @@\n-ExecStart=/usr/bin/demo\n+ExecStart")
+        );
+
+        fs::copy(&source, &installed).unwrap();
+        assert_eq!(app.diff_installed(3).unwrap(), "");
+        fs::remove_dir_all(&repo_dir).unwrap();
+    }
+
+    #[test]
+    fn status_is_not_installed_without_running_systemctl() {
+        let repo_dir = test_repo("status-not-installed");
+        fs::remove_file(repo_dir.join("installed/demo.service")).unwrap();
+        let runner = rc::Rc::new(MockCommandRunner::default());
+
+        let app = test_manager(&repo_dir, &runner).load_app("demo").unwrap();
+        assert_eq!(app.get_status().unwrap(), AppStatus::NotInstalled);
+        fs::remove_dir_all(&repo_dir).unwrap();
     }
 }
@@ -0,0 +1,75 @@
+use anyhow::{Context, Result, anyhow, bail};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::Write;
+use std::{fs, path};
+
+pub const MANIFEST_FILE_NAME: &str = "units.toml";
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+pub struct Manifest {
+    #[serde(default)]
+    pub apps: Vec<ManifestApp>,
+    #[serde(skip)]
+    pub path: path::PathBuf,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ManifestApp {
+    pub name: String,
+    // relative to the directory holding the manifest
+    pub path: path::PathBuf,
+}
+
+impl Manifest {
+    pub fn load(manifest_path: &path::Path) -> Result<Self> {
+        let manifest_str = fs::read_to_string(manifest_path)
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+        let mut manifest: Manifest = toml::from_str(&manifest_str)
+            .with_context(|| format!("Failed to parse {}", manifest_path.display()))?;
+        manifest.path = manifest_path.to_path_buf();
+
+        let mut names = HashSet::new();
+        for app in &manifest.apps {
+            if !names.insert(&app.name) {
+                bail!(
+                    "App {} is listed more than once in {}",
+                    app.name,
+                    manifest_path.display()
+                );
+            }
+            if app.path.is_absolute() {
+                bail!(
+                    "Path {} of app {} in {} must be relative",
+                    app.path.display(),
+                    app.name,
+                    manifest_path.display()
+                );
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn find(&self, app_name: &str) -> Option<&ManifestApp> {
+        self.apps.iter().find(|app| app.name == app_name)
+    }
+
+    // appends an entry instead of rewriting the file, so comments are kept
+    pub fn append(&self, app_name: &str, app_path: &path::Path) -> Result<()> {
+        let app_path = app_path
+            .to_str()
+            .ok_or_else(|| anyhow!("{} is not valid UTF-8", app_path.display()))?;
+        let entry = format!(
+            "\n[[apps]]\nname = {}\npath = {}\n",
+            toml::Value::from(app_name),
+            toml::Value::from(app_path)
+        );
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(entry.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt::Write;
+
+use crate::app::AppStatus;
+use crate::manager::StatusReport;
+
+const STATUSES: [AppStatus; 7] = [
+    AppStatus::NotInstalled,
+    AppStatus::Installed,
+    AppStatus::Stopped,
+    AppStatus::Running,
+    AppStatus::Failed,
+    AppStatus::StartLimitHit,
+    AppStatus::Masked,
+];
+
+pub fn render(reports: &[StatusReport]) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP units_app_status Current status of the app\n");
+    out.push_str("# TYPE units_app_status gauge\n");
+    for report in reports {
+        for status in &STATUSES {
+            let value = if *status == report.status { 1.0 } else { 0.0 };
+            let _ = writeln!(
+                out,
+                "units_app_status{{app=\"{}\",status=\"{}\"}} {value:.1}",
+                escape_label(&report.name),
+                status_label(status)
+            );
+        }
+    }
+
+    out.push_str("# HELP units_app_running Whether the app is running\n");
+    out.push_str("# TYPE units_app_running gauge\n");
+    for report in reports {
+        let running = u8::from(report.status == AppStatus::Running);
+        let _ = writeln!(
+            out,
+            "units_app_running{{app=\"{}\"}} {running}",
+            escape_label(&report.name)
+        );
+    }
+
+    out.push_str("# HELP units_app_memory_bytes Memory used by the app's service\n");
+    out.push_str("# TYPE units_app_memory_bytes gauge\n");
+    for report in reports {
+        if let Some(bytes) = report.memory_bytes {
+            let _ = writeln!(
+                out,
+                "units_app_memory_bytes{{app=\"{}\"}} {bytes}",
+                escape_label(&report.name)
+            );
+        }
+    }
+
+    out.push_str(
+        "# HELP units_app_security_exposure Exposure score from systemd-analyze security\n",
+    );
+    out.push_str("# TYPE units_app_security_exposure gauge\n");
+    for report in reports {
+        if let Some(score) = report.security_score {
+            let _ = writeln!(
+                out,
+                "units_app_security_exposure{{app=\"{}\"}} {score:.1}",
+                escape_label(&report.name)
+            );
+        }
+    }
+
+    out
+}
+
+// label values are quoted, so backslashes, quotes and newlines are escaped
+// as the Prometheus text format requires
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn status_label(status: &AppStatus) -> &'static str {
+    match status {
+        AppStatus::NotInstalled => "not_installed",
+        AppStatus::Installed => "installed",
+        AppStatus::Stopped => "stopped",
+        AppStatus::Running => "running",
+        AppStatus::Failed => "failed",
+        AppStatus::StartLimitHit => "start_limit_hit",
+        AppStatus::Masked => "masked",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_names_are_escaped_in_labels() {
+        let reports = [StatusReport {
+            name: String::from("a\"b\\c\nd"),
+            status: AppStatus::Running,
+            memory_bytes: Some(1024),
+            security_score: None,
+        }];
+        let rendered = render(&reports);
+        assert!(rendered.contains("units_app_running{app=\"a\\\"b\\\\c\\nd\"} 1\n"));
+        assert!(rendered.contains("units_app_memory_bytes{app=\"a\\\"b\\\\c\\nd\"} 1024\n"));
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::io::{Seek, Write};
+use std::{fs, path};
+
+use crate::undo::STATE_DIR_NAME;
+
+const PROGRESS_FILE_NAME: &str = "install_progress.toml";
+
+#[derive(Deserialize, Serialize)]
+pub struct InstallPlan {
+    pub apps: Vec<String>,
+    #[serde(default)]
+    pub completed: Vec<String>,
+    #[serde(default)]
+    pub env: Vec<String>,
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(default)]
+    pub allow_conflicts: bool,
+    #[serde(default)]
+    pub health_check: bool,
+    pub install_dir: Option<path::PathBuf>,
+}
+
+// the progress file stays locked for as long as this is alive, so two bulk
+// installs can't run at the same time
+pub struct InstallProgress {
+    pub plan: InstallPlan,
+    file: fs::File,
+    path: path::PathBuf,
+}
+
+impl InstallProgress {
+    pub fn start(repo_dir: &path::Path, plan: InstallPlan) -> Result<Self> {
+        let path = progress_path(repo_dir);
+        fs::create_dir_all(repo_dir.join(STATE_DIR_NAME))?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        lock(&file, &path)?;
+
+        let mut progress = InstallProgress { plan, file, path };
+        progress.save()?;
+        Ok(progress)
+    }
+
+    pub fn resume(repo_dir: &path::Path) -> Result<Option<Self>> {
+        let path = progress_path(repo_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        lock(&file, &path)?;
+
+        let plan_str = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let plan = toml::from_str(&plan_str)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(InstallProgress { plan, file, path }))
+    }
+
+    pub fn remaining(&self) -> Vec<String> {
+        self.plan
+            .apps
+            .iter()
+            .filter(|app| !self.plan.completed.contains(app))
+            .cloned()
+            .collect()
+    }
+
+    pub fn complete(&mut self, app_name: &str) -> Result<()> {
+        self.plan.completed.push(app_name.to_string());
+        self.save()
+    }
+
+    pub fn finish(self) -> Result<()> {
+        fs::remove_file(&self.path)
+            .with_context(|| format!("Failed to remove {}", self.path.display()))
+    }
+
+    fn save(&mut self) -> Result<()> {
+        // rewrite through the locked handle rather than replacing the file
+        let content = toml::to_string(&self.plan)?;
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        self.file
+            .write_all(content.as_bytes())
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+// whether a bulk install currently holds the progress file
+pub fn is_locked(repo_dir: &path::Path) -> Result<bool> {
+    let path = progress_path(repo_dir);
+    if !path.exists() {
+        return Ok(false);
+    }
+
+    let file =
+        fs::File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => Ok(false),
+        Err(fs::TryLockError::WouldBlock) => Ok(true),
+        Err(fs::TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+}
+
+fn lock(file: &fs::File, path: &path::Path) -> Result<()> {
+    match file.try_lock() {
+        Ok(()) => Ok(()),
+        Err(fs::TryLockError::WouldBlock) => {
+            bail!(
+                "Another install is in progress, {} is locked",
+                path.display()
+            )
+        }
+        Err(fs::TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+}
+
+fn progress_path(repo_dir: &path::Path) -> path::PathBuf {
+    repo_dir.join(STATE_DIR_NAME).join(PROGRESS_FILE_NAME)
+}
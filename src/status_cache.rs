@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::time;
+
+use crate::app::AppStatus;
+
+pub const DEFAULT_TTL: time::Duration = time::Duration::from_secs(2);
+
+pub struct StatusCache {
+    entries: HashMap<String, (AppStatus, time::Instant)>,
+    ttl: time::Duration,
+}
+
+impl StatusCache {
+    pub fn new(ttl: time::Duration) -> Self {
+        StatusCache {
+            entries: HashMap::new(),
+            ttl,
+        }
+    }
+
+    // a zero ttl means every lookup misses
+    pub fn disabled() -> Self {
+        StatusCache::new(time::Duration::ZERO)
+    }
+
+    pub fn get(&self, app_name: &str) -> Option<AppStatus> {
+        let (status, cached_at) = self.entries.get(app_name)?;
+        (cached_at.elapsed() < self.ttl).then(|| status.clone())
+    }
+
+    pub fn insert(&mut self, app_name: &str, status: AppStatus) {
+        if !self.ttl.is_zero() {
+            self.entries
+                .insert(app_name.to_string(), (status, time::Instant::now()));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
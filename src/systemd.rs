@@ -1,7 +1,8 @@
 use anyhow::Result;
-use std::process;
 
-pub fn is_active(service_name: &str, rootful: bool) -> Result<bool> {
+use crate::exec::CommandRunner;
+
+pub fn is_active(service_name: &str, rootful: bool, runner: &dyn CommandRunner) -> Result<bool> {
     let mut args = vec!["is-active", "--quiet", service_name];
     if rootful {
         args.insert(0, "systemctl");
@@ -10,15 +11,12 @@ pub fn is_active(service_name: &str, rootful: bool) -> Result<bool> {
     }
 
     let command = if rootful { "sudo" } else { "systemctl" };
-    let is_active = process::Command::new(command)
-        .args(args)
-        .status()
-        .map(|s| s.success())?;
+    let is_active = runner.run(command, &args).map(|o| o.status.success())?;
 
     Ok(is_active)
 }
 
-pub fn is_enabled(service_name: &str, rootful: bool) -> Result<bool> {
+pub fn is_enabled(service_name: &str, rootful: bool, runner: &dyn CommandRunner) -> Result<bool> {
     let mut args = vec!["is-enabled", "--quiet", service_name];
     if rootful {
         args.insert(0, "systemctl");
@@ -27,10 +25,21 @@ pub fn is_enabled(service_name: &str, rootful: bool) -> Result<bool> {
     }
 
     let command = if rootful { "sudo" } else { "systemctl" };
-    let is_active = process::Command::new(command)
-        .args(args)
-        .status()
-        .map(|s| s.success())?;
+    let is_active = runner.run(command, &args).map(|o| o.status.success())?;
 
     Ok(is_active)
 }
+
+pub fn is_failed(service_name: &str, rootful: bool, runner: &dyn CommandRunner) -> Result<bool> {
+    let mut args = vec!["is-failed", "--quiet", service_name];
+    if rootful {
+        args.insert(0, "systemctl");
+    } else {
+        args.insert(0, "--user");
+    }
+
+    let command = if rootful { "sudo" } else { "systemctl" };
+    let is_failed = runner.run(command, &args).map(|o| o.status.success())?;
+
+    Ok(is_failed)
+}
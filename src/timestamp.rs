@@ -0,0 +1,160 @@
+use std::time;
+
+// parses "Mon 2024-01-15 12:34:56 UTC"
+pub fn parse_systemd(value: &str) -> Option<time::SystemTime> {
+    let mut parts = value.split_whitespace();
+    let _weekday = parts.next()?;
+    let date = parts.next()?;
+    let clock = parts.next()?;
+    if parts.next()? != "UTC" {
+        return None;
+    }
+
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let clock: Vec<i64> = clock
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let (&[year, month, day], &[hours, minutes, seconds]) = (date.as_slice(), clock.as_slice())
+    else {
+        return None;
+    };
+
+    let secs = days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds;
+    Some(time::UNIX_EPOCH + time::Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+// formats as "2024-01-15T12:34:56Z"
+pub fn format_rfc3339(t: time::SystemTime) -> String {
+    let secs = t
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let secs = secs.rem_euclid(86400);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+// "2024-01-15T12:34:56Z" -> "2024-01-15 12:34:56 UTC", which journalctl --since accepts
+pub fn rfc3339_to_journal(value: &str) -> Option<String> {
+    let (date, time) = value.strip_suffix('Z')?.split_once('T')?;
+    Some(format!("{date} {time} UTC"))
+}
+
+pub fn now_rfc3339() -> String {
+    format_rfc3339(time::SystemTime::now())
+}
+
+// parses systemd time spans such as "30s", "1min 30s" or a plain microsecond count
+pub fn parse_timespan(value: &str) -> Option<time::Duration> {
+    if let Ok(usec) = value.parse::<u64>() {
+        return Some(time::Duration::from_micros(usec));
+    }
+
+    let mut total = time::Duration::ZERO;
+    for part in value.split_whitespace() {
+        let split = part.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (number, unit) = part.split_at(split);
+        let number: f64 = number.parse().ok()?;
+        let usec_per_unit = match unit {
+            "us" | "usec" => 1.0,
+            "ms" | "msec" => 1e3,
+            "s" | "sec" => 1e6,
+            "m" | "min" => 60e6,
+            "h" | "hr" => 3600e6,
+            "d" => 86400e6,
+            _ => return None,
+        };
+        total += time::Duration::from_micros((number * usec_per_unit) as u64);
+    }
+    Some(total)
+}
+
+// days since the unix epoch for a proleptic gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64) -> time::SystemTime {
+        time::UNIX_EPOCH + time::Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_parse_systemd() {
+        assert_eq!(parse_systemd("Thu 1970-01-01 00:00:00 UTC"), Some(at(0)));
+        assert_eq!(
+            parse_systemd("Thu 2024-02-29 12:00:00 UTC"),
+            Some(at(1_709_208_000))
+        );
+        assert_eq!(parse_systemd("Thu 2024-02-29 12:00:00 CET"), None);
+        assert_eq!(parse_systemd("n/a"), None);
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(at(0)), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(at(1_709_208_000)), "2024-02-29T12:00:00Z");
+        assert_eq!(format_rfc3339(at(1_709_251_199)), "2024-02-29T23:59:59Z");
+        assert_eq!(format_rfc3339(at(1_709_251_200)), "2024-03-01T00:00:00Z");
+    }
+
+    #[test]
+    fn test_civil_days_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        for days in [-719_468, -1, 0, 11_016, 19_782, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_parse_timespan() {
+        let secs = time::Duration::from_secs;
+        assert_eq!(parse_timespan("1min 30s"), Some(secs(90)));
+        assert_eq!(parse_timespan("30s"), Some(secs(30)));
+        assert_eq!(
+            parse_timespan("1h 500ms"),
+            Some(time::Duration::from_millis(3_600_500))
+        );
+        assert_eq!(parse_timespan("90000000"), Some(secs(90)));
+        assert_eq!(parse_timespan("infinity"), None);
+        assert_eq!(parse_timespan("5 parsecs"), None);
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path};
+
+pub const STATE_DIR_NAME: &str = ".units_state";
+const UNDO_STACK_FILE_NAME: &str = "undo_stack.toml";
+const MAX_UNDO_RECORDS: usize = 20;
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct UndoStack {
+    #[serde(default)]
+    pub records: Vec<UndoRecord>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UndoRecord {
+    pub operation: String,
+    pub app_name: String,
+    pub timestamp: String,
+    pub undo_data: UndoData,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UndoData {
+    Install {
+        files: Vec<path::PathBuf>,
+    },
+    Uninstall {
+        backup_dir: path::PathBuf,
+        files: Vec<path::PathBuf>,
+        // lets the files be put back even if the app was pruned, missing in
+        // records made by older versions
+        #[serde(default)]
+        systemd_dir: Option<path::PathBuf>,
+        #[serde(default)]
+        use_user: bool,
+        #[serde(default)]
+        units: Vec<String>,
+    },
+}
+
+impl UndoStack {
+    pub fn load(repo_dir: &path::Path) -> Result<Self> {
+        let stack_path = stack_path(repo_dir);
+        if !stack_path.exists() {
+            return Ok(UndoStack::default());
+        }
+
+        let stack_str = fs::read_to_string(&stack_path)
+            .with_context(|| format!("Failed to read {}", stack_path.display()))?;
+        let stack = toml::from_str(&stack_str)
+            .with_context(|| format!("Failed to parse {}", stack_path.display()))?;
+        Ok(stack)
+    }
+
+    pub fn save(&self, repo_dir: &path::Path) -> Result<()> {
+        let stack_path = stack_path(repo_dir);
+        fs::create_dir_all(repo_dir.join(STATE_DIR_NAME))?;
+        fs::write(&stack_path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", stack_path.display()))?;
+        Ok(())
+    }
+
+    pub fn push(&mut self, record: UndoRecord) {
+        self.records.push(record);
+        if self.records.len() > MAX_UNDO_RECORDS {
+            self.records.remove(0);
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<UndoRecord> {
+        self.records.pop()
+    }
+}
+
+fn stack_path(repo_dir: &path::Path) -> path::PathBuf {
+    repo_dir.join(STATE_DIR_NAME).join(UNDO_STACK_FILE_NAME)
+}
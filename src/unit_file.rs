@@ -0,0 +1,121 @@
+pub fn get_value(content: &str, section: &str, key: &str) -> Option<String> {
+    let mut in_section = false;
+    let mut value = None;
+
+    for (_, line) in logical_lines(content) {
+        if line.starts_with('[') && line.ends_with(']') {
+            in_section = line[1..line.len() - 1] == *section;
+            continue;
+        }
+
+        // later assignments override earlier ones, like systemd
+        if in_section
+            && let Some((k, v)) = line.split_once('=')
+            && k.trim() == key
+        {
+            value = Some(v.trim().to_string());
+        }
+    }
+
+    value
+}
+
+// joins lines ending in a backslash and drops comments and blank lines,
+// keeping the number of the line each logical line starts on
+pub fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if current.is_empty() && (line.is_empty() || line.starts_with('#') || line.starts_with(';'))
+        {
+            continue;
+        }
+        if current.is_empty() {
+            start = i + 1;
+        }
+
+        match line.strip_suffix('\\') {
+            Some(rest) => {
+                current.push_str(rest.trim_end());
+                current.push(' ');
+            }
+            None => {
+                current.push_str(line);
+                lines.push((start, current.trim().to_string()));
+                current.clear();
+            }
+        }
+    }
+    if !current.is_empty() {
+        lines.push((start, current.trim().to_string()));
+    }
+
+    lines
+}
+
+// splits an Environment= value into its assignments, which systemd quotes
+// when they contain spaces, e.g. `FOO="a b" "BAR=c d"`
+pub fn split_quoted(value: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if Some(c) == quote => quote = None,
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some('n') => word.push('\n'),
+                    Some('t') => word.push('\t'),
+                    Some(escaped) => word.push(escaped),
+                    None => break,
+                }
+            }
+            '"' | '\'' if quote.is_none() => {
+                quote = Some(c);
+                in_word = true;
+            }
+            c if c.is_whitespace() && quote.is_none() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_quoted() {
+        assert_eq!(
+            split_quoted(r#"PLAIN=1 FOO="a b" "BAR=c d" QUOTE="say \"hi\"" EMPTY="""#),
+            vec![
+                "PLAIN=1",
+                "FOO=a b",
+                "BAR=c d",
+                "QUOTE=say \"hi\"",
+                "EMPTY="
+            ]
+        );
+        assert!(split_quoted("  ").is_empty());
+    }
+}